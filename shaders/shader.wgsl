@@ -1,5 +1,5 @@
 struct VertexInput {
-    [[location(0)]] position: vec3<f32>;
+    [[location(0)]] position: vec2<f32>;
     [[location(1)]] tex_coords: vec2<f32>;
 };
 
@@ -8,22 +8,360 @@ struct VertexOutput {
     [[location(0)]] tex_coords: vec2<f32>;
 };
 
+struct Display {
+    // image pixels to clip space
+    transform: mat4x4<f32>;
+    // in stops
+    exposure: f32;
+    // 0: clamp, 1: Reinhard, 2: ACES
+    tonemap: u32;
+    // 0: nearest, 1: bilinear, 2: bicubic, 3: Lanczos
+    filter: u32;
+    // surface pixels per image pixel
+    scale: f32;
+    // image pixels to clip space for the blurred background
+    background_transform: mat4x4<f32>;
+    // surface pixels per checkerboard square
+    checker_size: f32;
+    // 1 if the color channels are already multiplied by alpha
+    premultiplied: u32;
+    // linear colors
+    checker_a: vec4<f32>;
+    checker_b: vec4<f32>;
+    // center and radius in surface pixels, then 0 for a circle or 1 for a square
+    loupe: vec4<f32>;
+    // size of the pane the image is drawn in, in pixels
+    surface: vec2<f32>;
+    // top left corner of that pane on the surface
+    origin: vec2<f32>;
+    // factor the difference between two images is multiplied by
+    amplification: f32;
+};
+
+// display-only color adjustments, on sRGB-encoded values
+struct Adjust {
+    brightness: f32;
+    contrast: f32;
+    gamma: f32;
+    saturation: f32;
+    invert: u32;
+};
+
+[[group(1), binding(0)]]
+var<uniform> display: Display;
+[[group(1), binding(3)]]
+var<uniform> adjust: Adjust;
+
 [[stage(vertex)]]
 fn vs_main(
     model: VertexInput,
 ) -> VertexOutput {
     var out: VertexOutput;
     out.tex_coords = model.tex_coords;
-    out.clip_position = vec4<f32>(model.position, 1.0);
+    out.clip_position = display.transform * vec4<f32>(model.position, 0.0, 1.0);
+    return out;
+}
+
+// the image stretched to cover the whole surface
+[[stage(vertex)]]
+fn vs_background(
+    model: VertexInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.tex_coords = model.tex_coords;
+    out.clip_position = display.background_transform * vec4<f32>(model.position, 0.0, 1.0);
     return out;
 }
 
 [[group(0), binding(0)]]
 var t_diffuse: texture_2d<f32>;
-[[group(0), binding(1)]]
-var s_diffuse: sampler;
 
+[[group(1), binding(1)]]
+var s_linear: sampler;
+[[group(1), binding(2)]]
+var s_nearest: sampler;
+
+let PI: f32 = 3.14159265;
+
+// Catmull-Rom
+fn cubic(x: f32) -> f32 {
+    let x = abs(x);
+    if (x < 1.0) {
+        return (1.5 * x - 2.5) * x * x + 1.0;
+    }
+    if (x < 2.0) {
+        return ((-0.5 * x + 2.5) * x - 4.0) * x + 2.0;
+    }
+    return 0.0;
+}
+
+fn sinc(x: f32) -> f32 {
+    if (abs(x) < 0.00001) {
+        return 1.0;
+    }
+    return sin(PI * x) / (PI * x);
+}
+
+fn lanczos(x: f32) -> f32 {
+    if (abs(x) >= 3.0) {
+        return 0.0;
+    }
+    return sinc(x) * sinc(x / 3.0);
+}
+
+fn kernel(x: f32) -> f32 {
+    if (display.filter == 2u) {
+        return cubic(x);
+    }
+    return lanczos(x);
+}
+
+// Resamples with a bicubic or Lanczos kernel. Downscales start from the closest larger mip
+// level and widen the kernel so that every texel under it contributes.
+fn convolve(tex_coords: vec2<f32>) -> vec4<f32> {
+    let level = clamp(i32(floor(-log2(display.scale))), 0, textureNumLevels(t_diffuse) - 1);
+    let size = textureDimensions(t_diffuse, level);
+    let stretch = max(1.0 / (display.scale * exp2(f32(level))), 1.0);
+    let radius = select(3.0, 2.0, display.filter == 2u) * stretch;
+    let center = tex_coords * vec2<f32>(size) - 0.5;
+    let first = vec2<i32>(floor(center - radius)) + vec2<i32>(1, 1);
+    let last = vec2<i32>(floor(center + radius));
+
+    var sum = vec4<f32>(0.0);
+    var total = 0.0;
+    for (var y: i32 = first.y; y <= last.y; y = y + 1) {
+        let wy = kernel((f32(y) - center.y) / stretch);
+        for (var x: i32 = first.x; x <= last.x; x = x + 1) {
+            let weight = kernel((f32(x) - center.x) / stretch) * wy;
+            let texel = clamp(vec2<i32>(x, y), vec2<i32>(0, 0), size - vec2<i32>(1, 1));
+            sum = sum + textureLoad(t_diffuse, texel, level) * weight;
+            total = total + weight;
+        }
+    }
+    return sum / total;
+}
+
+// Narkowicz's fit of the ACES filmic curve
+fn aces(x: vec3<f32>) -> vec3<f32> {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    return (x * (a * x + b)) / (x * (c * x + d) + e);
+}
+
+fn tonemap(color: vec3<f32>) -> vec3<f32> {
+    var rgb = color * exp2(display.exposure);
+    if (display.tonemap == 1u) {
+        rgb = rgb / (rgb + 1.0);
+    } else if (display.tonemap == 2u) {
+        rgb = aces(rgb);
+    }
+    return clamp(rgb, vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+fn to_srgb(c: vec3<f32>) -> vec3<f32> {
+    return select(1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055, c * 12.92, c <= vec3<f32>(0.0031308));
+}
+
+fn to_linear(c: vec3<f32>) -> vec3<f32> {
+    return select(pow((c + 0.055) / 1.055, vec3<f32>(2.4)), c / 12.92, c <= vec3<f32>(0.04045));
+}
+
+fn adjusted(color: vec3<f32>) -> vec3<f32> {
+    var c = to_srgb(color);
+    c = (c - 0.5) * adjust.contrast + 0.5 + adjust.brightness;
+    c = pow(clamp(c, vec3<f32>(0.0), vec3<f32>(1.0)), vec3<f32>(1.0 / adjust.gamma));
+    let luma = dot(c, vec3<f32>(0.2126, 0.7152, 0.0722));
+    c = clamp(mix(vec3<f32>(luma), c, adjust.saturation), vec3<f32>(0.0), vec3<f32>(1.0));
+    if (adjust.invert == 1u) {
+        c = 1.0 - c;
+    }
+    return to_linear(c);
+}
+
+// the resampled texel with straight alpha
+fn sample(tex_coords: vec2<f32>) -> vec4<f32> {
+    var color: vec4<f32>;
+    if (display.filter == 0u) {
+        color = textureSample(t_diffuse, s_nearest, tex_coords);
+    } else if (display.filter == 1u) {
+        color = textureSample(t_diffuse, s_linear, tex_coords);
+    } else {
+        color = convolve(tex_coords);
+    }
+    if (display.premultiplied == 1u && color.a > 0.0) {
+        color = vec4<f32>(color.rgb / color.a, color.a);
+    }
+    return color;
+}
+
+fn gray(v: f32) -> vec4<f32> {
+    return vec4<f32>(adjusted(tonemap(vec3<f32>(v))), 1.0);
+}
+
+// blended over the background by the pipeline
 [[stage(fragment)]]
 fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
-    return textureSample(t_diffuse, s_diffuse, in.tex_coords);
-}
\ No newline at end of file
+    let color = sample(in.tex_coords);
+    return vec4<f32>(adjusted(tonemap(color.rgb)), color.a);
+}
+
+// single channels as opaque grayscale
+
+[[stage(fragment)]]
+fn fs_red(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return gray(sample(in.tex_coords).r);
+}
+
+[[stage(fragment)]]
+fn fs_green(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return gray(sample(in.tex_coords).g);
+}
+
+[[stage(fragment)]]
+fn fs_blue(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return gray(sample(in.tex_coords).b);
+}
+
+[[stage(fragment)]]
+fn fs_alpha(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    // alpha isn't sRGB-encoded, show it as stored
+    return vec4<f32>(to_linear(vec3<f32>(sample(in.tex_coords).a)), 1.0);
+}
+
+// the colors without transparency, tinted red where they would be transparent
+[[stage(fragment)]]
+fn fs_alpha_overlay(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let color = sample(in.tex_coords);
+    let rgb = adjusted(tonemap(color.rgb));
+    return vec4<f32>(mix(rgb, vec3<f32>(1.0, 0.0, 0.0), (1.0 - color.a) * 0.6), 1.0);
+}
+
+fn checker(position: vec2<f32>) -> vec4<f32> {
+    let square = vec2<i32>(floor(position / display.checker_size));
+    return select(display.checker_a, display.checker_b, ((square.x + square.y) & 1) == 1);
+}
+
+// squares fixed to the screen, drawn under the image
+[[stage(fragment)]]
+fn fs_checker(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return checker(in.clip_position.xy);
+}
+
+let LOUPE_BORDER: f32 = 2.0;
+
+// 0 outside the loupe, 1 on its border, 2 inside
+fn loupe_area(position: vec2<f32>) -> u32 {
+    let d = position - display.loupe.xy;
+    var distance = length(d);
+    if (display.loupe.w == 1.0) {
+        distance = max(abs(d.x), abs(d.y));
+    }
+    if (distance > display.loupe.z) {
+        return 0u;
+    }
+    if (distance > display.loupe.z - LOUPE_BORDER) {
+        return 1u;
+    }
+    return 2u;
+}
+
+// a triangle covering the square around the loupe, the rest is cut by the scissor rect
+[[stage(vertex)]]
+fn vs_loupe_frame([[builtin(vertex_index)]] index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    let corner = vec2<f32>(f32(index / 2u), f32(index & 1u)) * 2.0;
+    let position = display.loupe.xy + (corner * 2.0 - 1.0) * display.loupe.z - display.origin;
+    out.tex_coords = corner;
+    out.clip_position = vec4<f32>(
+        position.x / display.surface.x * 2.0 - 1.0,
+        1.0 - position.y / display.surface.y * 2.0,
+        0.0,
+        1.0,
+    );
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_loupe_frame(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let area = loupe_area(in.clip_position.xy);
+    if (area == 0u) {
+        discard;
+    }
+    if (area == 1u) {
+        return vec4<f32>(0.8, 0.8, 0.8, 1.0);
+    }
+    return checker(in.clip_position.xy);
+}
+
+// the image inside the loupe frame, blended over its checkerboard
+[[stage(fragment)]]
+fn fs_loupe(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    if (loupe_area(in.clip_position.xy) != 2u) {
+        discard;
+    }
+    let color = sample(in.tex_coords);
+    return vec4<f32>(adjusted(tonemap(color.rgb)), color.a);
+}
+
+// the tile of the other image at the same place, for the difference
+[[group(2), binding(0)]]
+var t_other: texture_2d<f32>;
+
+// black through red and yellow to white as `v` goes from 0 to 1
+fn heat(v: f32) -> vec4<f32> {
+    let v = clamp(v, 0.0, 1.0) * 3.0;
+    let rgb = clamp(vec3<f32>(v, v - 1.0, v - 2.0), vec3<f32>(0.0), vec3<f32>(1.0));
+    return vec4<f32>(to_linear(rgb), 1.0);
+}
+
+// the largest difference between the channels of two images, texel by texel
+[[stage(fragment)]]
+fn fs_difference(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let size = textureDimensions(t_diffuse);
+    let other_size = textureDimensions(t_other);
+    let position = in.tex_coords * vec2<f32>(size);
+    let texel = min(vec2<i32>(position), size - vec2<i32>(1, 1));
+    if (texel.x >= other_size.x || texel.y >= other_size.y) {
+        // outside of the other image
+        return heat(1.0);
+    }
+    let d = abs(textureLoad(t_diffuse, texel, 0) - textureLoad(t_other, texel, 0));
+    return heat(max(max(d.r, d.g), max(d.b, d.a)) * display.amplification);
+}
+
+// a triangle covering the viewport, cut down by the scissor rect
+[[stage(vertex)]]
+fn vs_fullscreen([[builtin(vertex_index)]] index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    let corner = vec2<f32>(f32(index / 2u), f32(index & 1u)) * 2.0;
+    out.tex_coords = corner;
+    out.clip_position = vec4<f32>(corner.x * 2.0 - 1.0, 1.0 - corner.y * 2.0, 0.0, 1.0);
+    return out;
+}
+
+// the line between the two images of a wipe
+[[stage(fragment)]]
+fn fs_wipe(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return vec4<f32>(0.8, 0.8, 0.8, 1.0);
+}
+
+// a dimmed, heavily downscaled copy of the image
+[[stage(fragment)]]
+fn fs_blur(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let size = vec2<f32>(textureDimensions(t_diffuse));
+    // about 16 texels across
+    let level = max(log2(max(size.x, size.y) / 16.0), 0.0);
+    let texel = exp2(level) / size;
+    var sum = vec4<f32>(0.0);
+    for (var y: i32 = -1; y <= 1; y = y + 1) {
+        for (var x: i32 = -1; x <= 1; x = x + 1) {
+            let offset = vec2<f32>(f32(x), f32(y)) * texel;
+            sum = sum + textureSampleLevel(t_diffuse, s_linear, in.tex_coords + offset, level);
+        }
+    }
+    let color = sum / 9.0;
+    return vec4<f32>(tonemap(color.rgb) * color.a * 0.5, 1.0);
+}
@@ -0,0 +1,42 @@
+struct Screen {
+    size: vec2<f32>;
+};
+
+struct VertexInput {
+    [[location(0)]] position: vec2<f32>;
+    [[location(1)]] tex_coords: vec2<f32>;
+    [[location(2)]] color: vec4<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] tex_coords: vec2<f32>;
+    [[location(1)]] color: vec4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> screen: Screen;
+
+[[stage(vertex)]]
+fn vs_main(
+    model: VertexInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.tex_coords = model.tex_coords;
+    out.color = model.color;
+    // pixels from the top-left corner to clip space
+    let ndc = model.position / screen.size * 2.0 - 1.0;
+    out.clip_position = vec4<f32>(ndc.x, -ndc.y, 0.0, 1.0);
+    return out;
+}
+
+[[group(0), binding(1)]]
+var t_atlas: texture_2d<f32>;
+[[group(0), binding(2)]]
+var s_atlas: sampler;
+
+// thumbnails are drawn in white, plain rectangles sample the white cell
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return textureSample(t_atlas, s_atlas, in.tex_coords) * in.color;
+}
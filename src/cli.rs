@@ -1,11 +1,112 @@
 use clap::App;
 use clap::Arg;
+use clap::SubCommand;
+
+use yume::player::{
+    renderer::{Background, Filter},
+    zoom::ZoomMode,
+};
+
+/// `arg` limited to `names`.
+fn one_of(
+    arg: Arg<'static, 'static>,
+    names: impl Iterator<Item = &'static str>,
+) -> Arg<'static, 'static> {
+    names.fold(arg, Arg::possible_value)
+}
 
 pub fn app() -> App<'static, 'static> {
-    App::new(env!("CARGO_PKG_NAME")).arg(
-        Arg::with_name("image")
-            .help("image to open")
-            .multiple(true)
-            .required(false),
-    )
+    App::new(env!("CARGO_PKG_NAME"))
+        .arg(
+            Arg::with_name("image")
+                .help("image to open")
+                .multiple(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("compare")
+                .long("compare")
+                .takes_value(true)
+                .value_name("PATH")
+                .multiple(true)
+                .number_of_values(1)
+                .max_values(3)
+                .help("show images from PATH next to the main ones, up to three times"),
+        )
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("read settings from FILE instead of the default config.toml"),
+        )
+        .arg(
+            one_of(
+                Arg::with_name("filter"),
+                Filter::ALL.iter().map(|f| f.name()),
+            )
+            .long("filter")
+            .takes_value(true)
+            .help("resampling filter used to scale images"),
+        )
+        .arg(
+            one_of(
+                Arg::with_name("background"),
+                Background::ALL.iter().map(|b| b.name()),
+            )
+            .long("background")
+            .takes_value(true)
+            .help("what to show behind transparent images"),
+        )
+        .arg(
+            one_of(
+                Arg::with_name("zoom-mode"),
+                ZoomMode::ALL.iter().map(|m| m.name()),
+            )
+            .long("zoom-mode")
+            .takes_value(true)
+            .help("how images are scaled to the window"),
+        )
+        .arg(
+            Arg::with_name("zoom")
+                .long("zoom")
+                .takes_value(true)
+                .value_name("SCALE")
+                .conflicts_with("zoom-mode")
+                .validator(|v| match v.parse::<f32>() {
+                    Ok(scale) if scale > 0.0 => Ok(()),
+                    _ => Err(format!("{} is not a positive number", v)),
+                })
+                .help("show every image at SCALE, 1 being 100%"),
+        )
+        .arg(
+            Arg::with_name("pixel-perfect").long("pixel-perfect").help(
+                "only zoom to whole multiples of 100% or fractions like 1/2, without smoothing",
+            ),
+        )
+        .arg(
+            Arg::with_name("no-auto-orient")
+                .long("no-auto-orient")
+                .help("show images as stored, ignoring their EXIF orientation"),
+        )
+        .arg(
+            Arg::with_name("no-hud")
+                .long("no-hud")
+                .help("start with the file name and zoom hidden"),
+        )
+        .arg(
+            Arg::with_name("no-color-manage")
+                .long("no-color-manage")
+                .help("ignore embedded ICC profiles instead of converting to sRGB"),
+        )
+        .subcommand(
+            SubCommand::with_name("info")
+                .about("print image metadata as JSON")
+                .arg(
+                    Arg::with_name("files")
+                        .help("images or directories to inspect")
+                        .multiple(true)
+                        .required(true),
+                ),
+        )
 }
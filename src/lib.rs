@@ -1,3 +1,3 @@
 pub mod player;
 
-pub(crate) use player::renderer::texture::Rgba8Image;
+pub(crate) use player::renderer::texture::{Pixels, Rgba8Image};
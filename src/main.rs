@@ -2,12 +2,17 @@
 
 mod cli;
 
+use clap::ArgMatches;
+use std::path::PathBuf;
 use winit::{
     dpi::LogicalSize,
     event_loop::EventLoop,
     window::{Icon, WindowBuilder},
 };
-use yume::player::Player;
+use yume::player::{
+    playlist::{self, metadata::Metadata},
+    Player,
+};
 
 const HEIGHT: u32 = 540;
 const WIDTH: u32 = 960;
@@ -16,6 +21,10 @@ const ICON_BYTES: &[u8] = include_bytes!("../assets/icon.png");
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = cli::app().get_matches();
 
+    if let Some(args) = args.subcommand_matches("info") {
+        return info(args);
+    }
+
     let event_loop = EventLoop::new();
     let window = {
         let size = LogicalSize::new(WIDTH as f64, HEIGHT as f64);
@@ -33,3 +42,28 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     pl.run(event_loop)
 }
+
+fn info(args: &ArgMatches<'_>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut paths = Vec::new();
+    for p in args.values_of_os("files").into_iter().flatten() {
+        playlist::read_dir(&PathBuf::from(p), &mut paths)?;
+    }
+
+    let mut failed = false;
+    let mut metadata = Vec::with_capacity(paths.len());
+    for path in paths {
+        match Metadata::read(&path) {
+            Ok(m) => metadata.push(m),
+            Err(e) => {
+                eprintln!("{}: {}", path.display(), e);
+                failed = true;
+            }
+        }
+    }
+    println!("{}", serde_json::to_string_pretty(&metadata)?);
+
+    if failed {
+        std::process::exit(1);
+    }
+    Ok(())
+}
@@ -0,0 +1,193 @@
+use crate::player::playlist::Frame;
+use std::time::{Duration, Instant};
+
+/// Browsers treat tiny frame delays as "as fast as possible" mistakes and slow them down.
+const MIN_DELAY: Duration = Duration::from_millis(20);
+const FALLBACK_DELAY: Duration = Duration::from_millis(100);
+const MIN_SPEED: f32 = 1.0 / 16.0;
+const MAX_SPEED: f32 = 16.0;
+
+#[derive(Debug)]
+pub struct Animation {
+    frames: Vec<Frame>,
+    index: usize,
+    deadline: Option<Instant>,
+    paused: bool,
+    speed: f32,
+}
+
+impl Animation {
+    pub fn new(frames: Vec<Frame>, speed: f32) -> Self {
+        assert!(!frames.is_empty());
+
+        let mut animation = Self {
+            frames,
+            index: 0,
+            deadline: None,
+            paused: false,
+            speed,
+        };
+        animation.schedule(Instant::now());
+        animation
+    }
+
+    pub fn is_animated(&self) -> bool {
+        self.frames.len() > 1
+    }
+
+    pub fn current(&self) -> &Frame {
+        &self.frames[self.index]
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The instant the next frame should be shown, if the animation is playing.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    /// Moves to the frame due at `now`, returning whether the current frame changed.
+    pub fn tick(&mut self, now: Instant) -> bool {
+        let mut changed = false;
+        while let Some(deadline) = self.deadline {
+            if now < deadline {
+                break;
+            }
+            self.index = (self.index + 1) % self.frames.len();
+            changed = true;
+            let next = deadline + self.delay();
+            // don't try to catch up after the event loop has been stalled for a long time
+            self.deadline = Some(if next < now { now + self.delay() } else { next });
+        }
+        changed
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        self.schedule(Instant::now());
+    }
+
+    /// Pauses the animation and moves `d` frames forward or backward.
+    pub fn step(&mut self, d: isize) {
+        let len = self.frames.len() as isize;
+        self.index = (self.index as isize + d).rem_euclid(len) as usize;
+        self.paused = true;
+        self.schedule(Instant::now());
+    }
+
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.clamp(MIN_SPEED, MAX_SPEED);
+        self.schedule(Instant::now());
+    }
+
+    fn delay(&self) -> Duration {
+        let delay = self.frames[self.index].delay;
+        let delay = if delay < MIN_DELAY {
+            FALLBACK_DELAY
+        } else {
+            delay
+        };
+        delay.div_f32(self.speed)
+    }
+
+    fn schedule(&mut self, now: Instant) {
+        self.deadline = if self.is_animated() && !self.paused {
+            Some(now + self.delay())
+        } else {
+            None
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Rgba8Image;
+
+    /// An animation with frames of `delays` milliseconds. Tests pick delays that f32 seconds
+    /// hold exactly, so deadlines compare equal after the speed division.
+    fn animation(delays: &[u64]) -> Animation {
+        let frames = delays
+            .iter()
+            .map(|&ms| {
+                let mut frame = Frame::still(Rgba8Image::new(1, 1).into());
+                frame.delay = Duration::from_millis(ms);
+                frame
+            })
+            .collect();
+        Animation::new(frames, 1.0)
+    }
+
+    #[test]
+    fn tick_advances_from_the_deadline() {
+        let mut animation = animation(&[125, 250, 500]);
+        let first = animation.deadline().unwrap();
+        assert!(!animation.tick(first - Duration::from_millis(1)));
+        assert_eq!(animation.index(), 0);
+
+        // late by a bit, the next frame keeps the original rhythm
+        assert!(animation.tick(first + Duration::from_millis(5)));
+        assert_eq!(animation.index(), 1);
+        assert_eq!(
+            animation.deadline(),
+            Some(first + Duration::from_millis(250))
+        );
+    }
+
+    #[test]
+    fn tick_wraps_around() {
+        let mut animation = animation(&[125, 250]);
+        let first = animation.deadline().unwrap();
+        assert!(animation.tick(first));
+        let second = animation.deadline().unwrap();
+        assert!(animation.tick(second));
+        assert_eq!(animation.index(), 0);
+        assert_eq!(
+            animation.deadline(),
+            Some(second + Duration::from_millis(125))
+        );
+    }
+
+    #[test]
+    fn tick_skips_ahead_after_a_stall() {
+        let mut animation = animation(&[125, 250]);
+        let late = animation.deadline().unwrap() + Duration::from_secs(10);
+        assert!(animation.tick(late));
+        assert_eq!(animation.index(), 1);
+        assert_eq!(
+            animation.deadline(),
+            Some(late + Duration::from_millis(250))
+        );
+    }
+
+    #[test]
+    fn stills_and_paused_animations_have_no_deadline() {
+        assert_eq!(animation(&[125]).deadline(), None);
+        let mut animation = animation(&[125, 250]);
+        animation.step(-1);
+        assert!(animation.is_paused());
+        assert_eq!(animation.index(), 1);
+        assert_eq!(animation.deadline(), None);
+    }
+
+    #[test]
+    fn speed_is_clamped() {
+        let mut animation = animation(&[125, 250]);
+        animation.set_speed(1000.0);
+        assert_eq!(animation.speed(), MAX_SPEED);
+        animation.set_speed(0.0);
+        assert_eq!(animation.speed(), MIN_SPEED);
+        animation.set_speed(2.0);
+        assert_eq!(animation.speed(), 2.0);
+    }
+}
@@ -0,0 +1,129 @@
+use super::{
+    renderer::{loupe::LoupeShape, Background, Filter},
+    zoom::ZoomMode,
+};
+use clap::ArgMatches;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Settings read from `config.toml`, then overridden by command line options.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    /// Rotate and flip images according to their EXIF Orientation tag.
+    pub auto_orient: bool,
+    /// Convert images with an embedded ICC profile to sRGB.
+    pub color_manage: bool,
+    /// Resampling filter used when the image is scaled.
+    pub filter: Filter,
+    /// Show exact pixels above 100% zoom, whatever `filter` is.
+    pub nearest_when_magnified: bool,
+    /// Smallest zoom reachable with the wheel or keys, 1.0 being 100%.
+    pub min_zoom: f32,
+    /// Largest zoom reachable with the wheel or keys.
+    pub max_zoom: f32,
+    /// How images are scaled when they are opened or the window is resized.
+    pub zoom_mode: ZoomMode,
+    /// Scale used by the locked zoom mode.
+    pub locked_zoom: f32,
+    /// Keep the scale at whole multiples or fractions so pixels stay square and sharp.
+    pub pixel_perfect: bool,
+    /// Let the image glide on after a mouse drag is released.
+    pub kinetic_scrolling: bool,
+    /// What shows through transparent parts of the image.
+    pub background: Background,
+    /// sRGB color of the solid background and of the area around the image.
+    pub background_color: [u8; 3],
+    /// Size of the checkerboard squares in physical pixels.
+    pub checker_size: f32,
+    /// sRGB colors of the checkerboard squares.
+    pub checker_colors: [[u8; 3]; 2],
+    pub loupe_shape: LoupeShape,
+    /// Half the width of the loupe in physical pixels.
+    pub loupe_radius: f32,
+    /// Zoom of the loupe relative to the image.
+    pub loupe_zoom: f32,
+    /// Show the file name, position and zoom on top of the image.
+    pub hud: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            auto_orient: true,
+            color_manage: true,
+            filter: Filter::default(),
+            nearest_when_magnified: false,
+            min_zoom: 0.05,
+            max_zoom: 32.0,
+            zoom_mode: ZoomMode::default(),
+            locked_zoom: 1.0,
+            pixel_perfect: false,
+            kinetic_scrolling: false,
+            background: Background::default(),
+            background_color: [0, 0, 0],
+            checker_size: 8.0,
+            checker_colors: [[204, 204, 204], [153, 153, 153]],
+            loupe_shape: LoupeShape::default(),
+            loupe_radius: 100.0,
+            loupe_zoom: 4.0,
+            hud: true,
+        }
+    }
+}
+
+impl Config {
+    pub fn from_args(args: &ArgMatches<'_>) -> Self {
+        let mut config = match args.value_of("config") {
+            Some(path) => Self::read(path.as_ref()),
+            None => match default_path() {
+                Some(path) if path.exists() => Self::read(&path),
+                _ => Ok(Self::default()),
+            },
+        }
+        .unwrap_or_else(|e| {
+            eprintln!("failed to read config: {}", e);
+            Self::default()
+        });
+
+        if args.is_present("no-auto-orient") {
+            config.auto_orient = false;
+        }
+        if args.is_present("no-color-manage") {
+            config.color_manage = false;
+        }
+        if args.is_present("no-hud") {
+            config.hud = false;
+        }
+        if args.is_present("pixel-perfect") {
+            config.pixel_perfect = true;
+        }
+        if let Some(filter) = args.value_of("filter") {
+            // clap only accepts known names
+            config.filter = filter.parse().unwrap();
+        }
+        if let Some(background) = args.value_of("background") {
+            config.background = background.parse().unwrap();
+        }
+        if let Some(mode) = args.value_of("zoom-mode") {
+            config.zoom_mode = mode.parse().unwrap();
+        }
+        if let Some(zoom) = args.value_of("zoom") {
+            // checked by the validator
+            config.zoom_mode = ZoomMode::Locked;
+            config.locked_zoom = zoom.parse().unwrap();
+        }
+        config
+    }
+
+    pub fn read(path: &Path) -> Result<Self> {
+        Ok(toml::from_str(&std::fs::read_to_string(path)?)?)
+    }
+}
+
+/// `$XDG_CONFIG_HOME/yume/config.toml` or the platform equivalent.
+pub fn default_path() -> Option<PathBuf> {
+    Some(dirs::config_dir()?.join("yume").join("config.toml"))
+}
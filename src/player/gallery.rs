@@ -0,0 +1,188 @@
+use super::thumbnail::{self, Thumbnailer};
+use std::{
+    collections::HashSet,
+    ops::Range,
+    time::{Duration, Instant},
+};
+use ultraviolet::Vec2;
+
+/// Space around each thumbnail in pixels.
+const MARGIN: f32 = 8.0;
+/// Distance between the origins of neighbouring cells.
+const PITCH: f32 = thumbnail::SIZE as f32 + 2.0 * MARGIN;
+/// How often finished thumbnails are picked up while others are being decoded.
+const POLL: Duration = Duration::from_millis(50);
+
+/// The playlist as a scrollable grid of thumbnails.
+pub struct Gallery {
+    len: usize,
+    selected: usize,
+    /// Pixels scrolled down from the top of the grid.
+    scroll: f32,
+    pub thumbnailer: Option<Thumbnailer>,
+    /// Items whose thumbnails were dropped to make room for others.
+    evicted: HashSet<usize>,
+    /// Items last moved to the front of the thumbnail queue.
+    prioritized: Option<Range<usize>>,
+}
+
+impl Gallery {
+    pub fn new(len: usize) -> Self {
+        Self {
+            len,
+            selected: 0,
+            scroll: 0.0,
+            thumbnailer: None,
+            evicted: HashSet::new(),
+            prioritized: None,
+        }
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn columns(surface: Vec2) -> usize {
+        ((surface.x / PITCH) as usize).max(1)
+    }
+
+    /// Top left corner of the thumbnail square of item `index` on a surface of `surface` pixels.
+    pub fn cell(&self, index: usize, surface: Vec2) -> Vec2 {
+        let columns = Self::columns(surface);
+        // the grid is centered horizontally
+        let left = ((surface.x - columns as f32 * PITCH) / 2.0)
+            .max(0.0)
+            .floor();
+        Vec2::new(
+            left + (index % columns) as f32 * PITCH + MARGIN,
+            (index / columns) as f32 * PITCH + MARGIN - self.scroll,
+        )
+    }
+
+    /// Item whose cell is at `p` in surface pixels.
+    pub fn index_at(&self, p: Vec2, surface: Vec2) -> Option<usize> {
+        let first = self.cell(0, surface) - Vec2::broadcast(MARGIN);
+        let (column, row) = ((p.x - first.x) / PITCH, (p.y - first.y) / PITCH);
+        let columns = Self::columns(surface);
+        if column < 0.0 || row < 0.0 || column as usize >= columns {
+            return None;
+        }
+        let index = row as usize * columns + column as usize;
+        (index < self.len).then_some(index)
+    }
+
+    /// Items with any part of their cell on screen.
+    pub fn visible(&self, surface: Vec2) -> Range<usize> {
+        let columns = Self::columns(surface);
+        let first = (self.scroll / PITCH) as usize * columns;
+        let last = ((self.scroll + surface.y) / PITCH).ceil() as usize * columns;
+        first.min(self.len)..last.min(self.len)
+    }
+
+    pub fn scroll_rows(&mut self, rows: f32, surface: Vec2) {
+        self.scroll_by(rows * PITCH, surface);
+    }
+
+    fn scroll_by(&mut self, dy: f32, surface: Vec2) {
+        let rows = self.len.div_ceil(Self::columns(surface));
+        let max = (rows as f32 * PITCH - surface.y).max(0.0);
+        self.scroll = (self.scroll + dy).clamp(0.0, max);
+    }
+
+    /// Selects item `index` and scrolls it into view.
+    pub fn select(&mut self, index: usize, surface: Vec2) {
+        if self.len == 0 {
+            return;
+        }
+        self.selected = index.min(self.len - 1);
+        let top = self.cell(self.selected, surface).y - MARGIN;
+        if top < 0.0 {
+            self.scroll_by(top, surface);
+        } else if top + PITCH > surface.y {
+            self.scroll_by(top + PITCH - surface.y, surface);
+        }
+    }
+
+    /// Moves the selection by `columns` cells across and `rows` down.
+    pub fn step(&mut self, columns: isize, rows: isize, surface: Vec2) {
+        if self.len == 0 {
+            return;
+        }
+        let steps = columns + rows * Self::columns(surface) as isize;
+        let index = (self.selected as isize + steps).clamp(0, self.len as isize - 1);
+        self.select(index as usize, surface);
+    }
+
+    /// Notes that the thumbnail of item `index` was dropped.
+    pub fn evict(&mut self, index: usize) {
+        self.evicted.insert(index);
+    }
+
+    /// Decodes the dropped thumbnails of `items` again.
+    pub fn restore(&mut self, items: Range<usize>) {
+        if let Some(thumbnailer) = &mut self.thumbnailer {
+            for index in items {
+                if self.evicted.remove(&index) {
+                    thumbnailer.request(index);
+                }
+            }
+        }
+    }
+
+    /// Decodes the thumbnails of `items` first, once they differ from the last ones.
+    pub fn prioritize(&mut self, items: Range<usize>) {
+        if let Some(thumbnailer) = &self.thumbnailer {
+            if self.prioritized.as_ref() != Some(&items) {
+                thumbnailer.prioritize(items.clone());
+                self.prioritized = Some(items);
+            }
+        }
+    }
+
+    /// Stops decoding and forgets about dropped thumbnails, for when all of them are outdated.
+    pub fn forget_thumbnails(&mut self) {
+        self.thumbnailer = None;
+        self.evicted.clear();
+        self.prioritized = None;
+    }
+
+    /// The instant to check for new thumbnails, while some are still being decoded.
+    pub fn deadline(&self) -> Option<Instant> {
+        let thumbnailer = self.thumbnailer.as_ref()?;
+        (thumbnailer.pending() > 0).then(|| Instant::now() + POLL)
+    }
+
+    /// Number of whole rows on screen.
+    pub fn page_rows(surface: Vec2) -> isize {
+        ((surface.y / PITCH) as isize).max(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cells_are_found_where_they_are_drawn() {
+        let surface = Vec2::new(800.0, 600.0);
+        let mut gallery = Gallery::new(100);
+        gallery.scroll_by(250.0, surface);
+        for index in gallery.visible(surface) {
+            let center = gallery.cell(index, surface) + Vec2::broadcast(PITCH / 2.0 - MARGIN);
+            if center.y > 0.0 && center.y < surface.y {
+                assert_eq!(gallery.index_at(center, surface), Some(index));
+            }
+        }
+    }
+
+    #[test]
+    fn selection_stays_on_screen() {
+        let surface = Vec2::new(800.0, 600.0);
+        let mut gallery = Gallery::new(100);
+        gallery.step(0, 10, surface);
+        let cell = gallery.cell(gallery.selected(), surface);
+        assert!(cell.y >= 0.0 && cell.y + PITCH <= surface.y + MARGIN);
+        gallery.select(0, surface);
+        assert_eq!(gallery.cell(0, surface).y, MARGIN);
+    }
+}
@@ -0,0 +1,169 @@
+use std::time::{Duration, Instant};
+use ultraviolet::Vec2;
+
+/// Time between frames while gliding.
+const FRAME: Duration = Duration::from_millis(16);
+/// A drag that paused this long before the button was released doesn't glide.
+const RELEASE_WINDOW: Duration = Duration::from_millis(60);
+/// Fraction of the speed lost per second.
+const FRICTION: f32 = 0.95;
+/// Gliding stops below this many pixels per second.
+const MIN_SPEED: f32 = 20.0;
+
+/// Keeps the image moving after a mouse drag is released, slowing it down over time.
+#[derive(Debug)]
+pub struct Kinetic {
+    /// Screen pixels per second.
+    velocity: Vec2,
+    /// Time of the last drag or glide step.
+    last: Instant,
+    gliding: bool,
+}
+
+impl Kinetic {
+    pub fn new() -> Self {
+        Self {
+            velocity: Vec2::zero(),
+            last: Instant::now(),
+            gliding: false,
+        }
+    }
+
+    /// Records a drag by `delta` screen pixels.
+    pub fn drag(&mut self, delta: Vec2, now: Instant) {
+        self.gliding = false;
+        if delta == Vec2::zero() {
+            return;
+        }
+        let dt = now.duration_since(self.last).as_secs_f32();
+        self.last = now;
+        if dt > 0.0 && dt < RELEASE_WINDOW.as_secs_f32() {
+            // smooth out uneven event timing
+            self.velocity = self.velocity * 0.5 + delta / dt * 0.5;
+        } else {
+            self.velocity = Vec2::zero();
+        }
+    }
+
+    /// Starts gliding at the speed of the drag, if it was still moving.
+    pub fn release(&mut self, now: Instant) {
+        self.gliding =
+            now.duration_since(self.last) < RELEASE_WINDOW && self.velocity.mag() >= MIN_SPEED;
+        self.last = now;
+    }
+
+    pub fn stop(&mut self) {
+        self.gliding = false;
+        self.velocity = Vec2::zero();
+    }
+
+    /// The instant the next glide step is due, while gliding.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.gliding.then(|| self.last + FRAME)
+    }
+
+    /// Distance to move since the previous step, while gliding.
+    pub fn tick(&mut self, now: Instant) -> Option<Vec2> {
+        if !self.gliding {
+            return None;
+        }
+        let dt = now.duration_since(self.last).as_secs_f32();
+        self.last = now;
+        self.velocity *= (1.0 - FRICTION).powf(dt);
+        if self.velocity.mag() < MIN_SPEED {
+            self.stop();
+            return None;
+        }
+        Some(self.velocity * dt)
+    }
+}
+
+impl Default for Kinetic {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEP: Duration = Duration::from_millis(10);
+
+    fn assert_close(a: Vec2, b: Vec2) {
+        assert!((a - b).mag() < 1e-2, "{:?} != {:?}", a, b);
+    }
+
+    /// A drag of 10 pixels to the right every 10 ms, released right after the last one.
+    fn dragged(start: Instant) -> (Kinetic, Instant) {
+        let mut kinetic = Kinetic {
+            last: start,
+            ..Kinetic::new()
+        };
+        let mut now = start;
+        for _ in 0..2 {
+            now += STEP;
+            kinetic.drag(Vec2::new(10.0, 0.0), now);
+        }
+        (kinetic, now)
+    }
+
+    #[test]
+    fn drags_smooth_the_velocity() {
+        let start = Instant::now();
+        let (kinetic, _) = dragged(start);
+        // half of 1000 px/s, then halfway from there to 1000
+        assert_close(kinetic.velocity, Vec2::new(750.0, 0.0));
+
+        // a drag after a pause starts from rest
+        let mut kinetic = Kinetic {
+            last: start,
+            ..Kinetic::new()
+        };
+        kinetic.drag(Vec2::new(10.0, 0.0), start + RELEASE_WINDOW * 2);
+        assert_eq!(kinetic.velocity, Vec2::zero());
+    }
+
+    #[test]
+    fn release_inside_the_window_glides() {
+        let (mut kinetic, now) = dragged(Instant::now());
+        let released = now + RELEASE_WINDOW / 2;
+        kinetic.release(released);
+        assert_eq!(kinetic.deadline(), Some(released + FRAME));
+    }
+
+    #[test]
+    fn release_after_the_window_stays() {
+        let (mut kinetic, now) = dragged(Instant::now());
+        let released = now + RELEASE_WINDOW;
+        kinetic.release(released);
+        assert_eq!(kinetic.deadline(), None);
+        assert_eq!(kinetic.tick(released + FRAME), None);
+    }
+
+    #[test]
+    fn friction_slows_down_until_stopping() {
+        let (mut kinetic, now) = dragged(Instant::now());
+        kinetic.release(now);
+        let dt = 0.1;
+        let step = kinetic.tick(now + Duration::from_secs_f32(dt)).unwrap();
+        let speed = 750.0 * (1.0 - FRICTION).powf(dt);
+        assert_close(step, Vec2::new(speed * dt, 0.0));
+
+        // two seconds later the speed is below `MIN_SPEED`
+        let later = now + Duration::from_secs_f32(dt + 2.0);
+        assert_eq!(kinetic.tick(later), None);
+        assert_eq!(kinetic.deadline(), None);
+        assert_eq!(kinetic.tick(later + FRAME), None);
+    }
+
+    #[test]
+    fn stop_ends_the_glide() {
+        let (mut kinetic, now) = dragged(Instant::now());
+        kinetic.release(now);
+        assert!(kinetic.deadline().is_some());
+        kinetic.stop();
+        assert_eq!(kinetic.deadline(), None);
+        assert_eq!(kinetic.tick(now + FRAME), None);
+    }
+}
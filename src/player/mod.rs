@@ -1,9 +1,32 @@
+pub mod animation;
+pub mod config;
+pub mod gallery;
+pub mod kinetic;
 pub mod playlist;
 pub mod renderer;
+pub mod thumbnail;
+pub mod zoom;
 
+use animation::Animation;
 use clap::ArgMatches;
-use playlist::Playlist;
-use renderer::{texture::Rgba8Image, Pan, Renderer};
+use config::Config;
+use gallery::Gallery;
+use kinetic::Kinetic;
+use playlist::orientation::Orientation;
+use playlist::{DecodeOptions, Frame, Playlist};
+use renderer::{
+    adjust::{Adjustment, Adjustments},
+    loupe::Loupe,
+    texture::{Rgba8Image, Texel},
+    view::View,
+    Comparison, Renderer,
+};
+use std::{
+    f32::consts::FRAC_PI_2,
+    time::{Duration, Instant},
+};
+use thumbnail::Thumbnailer;
+use ultraviolet::Vec2;
 use winit::{
     dpi::PhysicalSize,
     event::{Event, VirtualKeyCode, WindowEvent},
@@ -11,42 +34,165 @@ use winit::{
     window::Window,
 };
 use winit_input_helper::WinitInputHelper;
+use zoom::ZoomMode;
+
+/// Zoom factor of one wheel notch or key press.
+const ZOOM_STEP: f32 = 1.25;
+/// How long status messages stay on screen.
+const MESSAGE_TIME: Duration = Duration::from_secs(2);
+/// Linear colors of the frame around the selected thumbnail and of missing thumbnails.
+const SELECTION_COLOR: [f32; 4] = [0.3, 0.5, 0.9, 1.0];
+const PLACEHOLDER_COLOR: [f32; 4] = [0.1, 0.1, 0.1, 1.0];
 
 pub struct Player {
     renderer: Renderer,
     playlist: Playlist,
     window: Window,
     input: WinitInputHelper,
+    animation: Animation,
+    /// Lines of the metadata overlay, `None` while it is hidden.
+    info: Option<Vec<String>>,
+    config: Config,
+    zoom_mode: ZoomMode,
+    locked_zoom: f32,
+    /// Whether the user zoomed or panned since the zoom mode was applied.
+    view_changed: bool,
+    kinetic: Kinetic,
+    /// Adjustment changed by the arrow keys.
+    adjustment: Adjustment,
+    /// Show the adjustments panel even when nothing is adjusted.
+    show_adjustments: bool,
+    /// Show the pixel under the cursor.
+    inspector: bool,
+    /// Shape and zoom of the loupe, which follows the cursor.
+    loupe: Loupe,
+    show_loupe: bool,
+    /// Playlists given with `--compare`, moved along with `playlist`.
+    compare: Vec<Playlist>,
+    /// Number of panes, 1 when not comparing.
+    panes: usize,
+    /// First frames of the images shown next to the current one, one per pane, `None` where
+    /// there is no image.
+    compare_images: Vec<Option<Frame>>,
+    /// Whether the mouse is dragging the wipe line rather than the image.
+    wiping: bool,
+    /// The playlist as thumbnails, shown in place of the image while the renderer is in
+    /// gallery mode.
+    gallery: Gallery,
+    show_hud: bool,
+    /// File name, position and page shown by the HUD, updated with the window title.
+    hud_title: String,
+    /// Status message and when it disappears.
+    message: Option<(String, Instant)>,
 }
 
 impl Player {
     pub async fn new(window: Window, arg: ArgMatches<'_>) -> Self {
-        let (playlist, renderer) = match arg.value_of("image") {
-            Some(p) => {
-                let mut sources = Vec::new();
+        let config = Config::from_args(&arg);
+        let open = |path: Option<&str>| {
+            let mut sources = Vec::new();
+            if let Some(p) = path {
                 playlist::read_dir(p.as_ref(), &mut sources).unwrap();
-                let playlist = Playlist::new(sources);
-                let img = playlist.current_image().unwrap();
-                let renderer = Renderer::new(&window, &img).await;
-                (playlist, renderer)
+            }
+            let mut playlist = Playlist::new(sources);
+            playlist.set_auto_orient(config.auto_orient);
+            playlist.set_options(DecodeOptions {
+                color_manage: config.color_manage,
+            });
+            playlist
+        };
+        let playlist = open(arg.value_of("image"));
+        let (renderer, animation) = match arg.value_of("image") {
+            Some(_) => {
+                let animation = Animation::new(playlist.current_frames().unwrap(), 1.0);
+                let renderer = Renderer::new(&window, &animation.current().buffer).await;
+                (renderer, animation)
             }
             None => {
-                let playlist = Playlist::new(vec![]);
-                (playlist, Renderer::idle(&window).await)
+                let animation =
+                    Animation::new(vec![Frame::still(Rgba8Image::new(1, 1).into())], 1.0);
+                (Renderer::idle(&window).await, animation)
             }
         };
+        let compare: Vec<_> = arg
+            .values_of("compare")
+            .into_iter()
+            .flatten()
+            .map(|path| open(Some(path)))
+            .collect();
+        let gallery = Gallery::new(playlist.len());
 
-        Self {
+        let mut player = Self {
             renderer,
             playlist,
             window,
             input: WinitInputHelper::new(),
-        }
+            animation,
+            info: None,
+            zoom_mode: config.zoom_mode,
+            locked_zoom: config.locked_zoom,
+            view_changed: false,
+            kinetic: Kinetic::new(),
+            adjustment: Adjustment::default(),
+            show_adjustments: false,
+            inspector: false,
+            loupe: Loupe {
+                center: Vec2::zero(),
+                radius: config.loupe_radius,
+                zoom: config.loupe_zoom,
+                shape: config.loupe_shape,
+            },
+            show_loupe: false,
+            panes: match compare.len() {
+                0 => 1,
+                1 => 2,
+                _ => 4,
+            },
+            compare,
+            compare_images: Vec::new(),
+            wiping: false,
+            gallery,
+            show_hud: config.hud,
+            hud_title: String::new(),
+            message: None,
+            config,
+        };
+        player.renderer.set_filter(player.config.filter);
+        player
+            .renderer
+            .set_nearest_when_magnified(player.config.nearest_when_magnified);
+        player
+            .renderer
+            .set_pixel_perfect(player.config.pixel_perfect);
+        player.renderer.set_background(player.config.background);
+        player
+            .renderer
+            .set_background_color(player.config.background_color);
+        player
+            .renderer
+            .set_checkerboard(player.config.checker_size, player.config.checker_colors);
+        player.update_compare();
+        player.apply_zoom_mode();
+        player.update_title();
+        player.check_precision();
+        player
     }
 
     pub fn run(mut self, event_loop: EventLoop<()>) -> ! {
         event_loop.run(move |event, _, control_flow| {
-            *control_flow = ControlFlow::Wait;
+            let deadline = [
+                self.animation.deadline(),
+                self.kinetic.deadline(),
+                self.gallery.deadline(),
+                self.message.as_ref().map(|(_, until)| *until),
+            ]
+            .into_iter()
+            .flatten()
+            .min();
+            *control_flow = match deadline {
+                Some(deadline) => ControlFlow::WaitUntil(deadline),
+                None => ControlFlow::Wait,
+            };
             match &event {
                 Event::RedrawRequested(_) => match self.render() {
                     Ok(_) => {}
@@ -62,6 +208,8 @@ impl Player {
                     ..
                 } => {
                     self.playlist.load_path(path).unwrap();
+                    self.gallery = Gallery::new(self.playlist.len());
+                    self.renderer.thumbnails.reset();
                     self.update_image();
                 }
                 _ => {}
@@ -80,19 +228,26 @@ impl Player {
                 }
 
                 self.handle_input();
+                self.update_animation();
+                self.update_kinetic();
+                self.update_loupe();
+                self.update_gallery();
+                self.update_overlay();
 
                 self.window.request_redraw();
             }
         })
     }
 
-    pub fn render(&self) -> Result<(), wgpu::SurfaceError> {
+    pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
         self.renderer.render()
     }
 
     pub fn resize(&mut self, size: PhysicalSize<u32>) {
-        self.renderer.scale = 1.0;
         self.renderer.resize(size);
+        if !self.view_changed {
+            self.apply_zoom_mode();
+        }
     }
 
     pub fn size(&self) -> PhysicalSize<u32> {
@@ -100,72 +255,864 @@ impl Player {
     }
 
     pub fn handle_input(&mut self) {
+        if self.input.key_pressed(VirtualKeyCode::G) {
+            self.toggle_gallery();
+        }
+
+        if self.renderer.gallery() {
+            self.handle_gallery_input();
+            return;
+        }
+
         if self.input.key_pressed(VirtualKeyCode::Left) {
-            self.playlist.advance(-1);
-            self.update_image();
+            self.advance(-1);
         }
 
         if self.input.key_pressed(VirtualKeyCode::Right) {
-            self.playlist.advance(1);
-            self.update_image();
+            self.advance(1);
+        }
+
+        if self.input.key_pressed(VirtualKeyCode::Tab) {
+            self.show_hud = !self.show_hud;
+        }
+
+        if self.input.key_pressed(VirtualKeyCode::F5) {
+            self.panes = match self.panes {
+                1 => 2,
+                2 => 4,
+                _ => 1,
+            };
+            self.update_compare();
+            self.update_transform();
+        }
+
+        if self.input.key_pressed(VirtualKeyCode::F6) {
+            self.toggle_comparison(Comparison::Wipe);
+        }
+
+        if self.input.key_pressed(VirtualKeyCode::F8) {
+            self.toggle_comparison(Comparison::Difference);
+        }
+
+        // the other image is shown while the key is down
+        let flicker = self.input.key_held(VirtualKeyCode::F7);
+        if flicker != self.renderer.flicker() {
+            self.renderer.set_flicker(flicker);
+            // without panes or a comparison there is no second image loaded yet
+            if self.panes < 2 && self.renderer.comparison().is_none() {
+                self.update_compare();
+            }
+        }
+
+        if self.input.key_pressed(VirtualKeyCode::PageDown) {
+            self.advance_page(1);
+        }
+
+        if self.input.key_pressed(VirtualKeyCode::PageUp) {
+            self.advance_page(-1);
         }
 
         if self.input.key_pressed(VirtualKeyCode::S) {
-            self.handle_scale_to_fit();
+            if self.input.held_control() {
+                self.save_orientation();
+            } else {
+                self.apply_zoom_mode();
+            }
+        }
+
+        let modes = [
+            (VirtualKeyCode::Key0, ZoomMode::Locked),
+            (VirtualKeyCode::Key1, ZoomMode::Actual),
+            (VirtualKeyCode::Key2, ZoomMode::Fit),
+            (VirtualKeyCode::Key3, ZoomMode::FitIfLarger),
+            (VirtualKeyCode::Key4, ZoomMode::Fill),
+            (VirtualKeyCode::Key5, ZoomMode::FitWidth),
+            (VirtualKeyCode::Key6, ZoomMode::FitHeight),
+        ];
+        for (key, mode) in modes {
+            if self.input.key_pressed(key) {
+                self.set_zoom_mode(mode);
+            }
+        }
+
+        if self.input.key_pressed(VirtualKeyCode::R) {
+            // fine rotation with control, quarter turns otherwise
+            let step = if self.input.held_control() {
+                1f32.to_radians()
+            } else {
+                FRAC_PI_2
+            };
+            let angle = if self.input.held_shift() { -step } else { step };
+            self.renderer.view.rotate(angle);
+            self.update_transform();
+        }
+
+        if self.input.key_pressed(VirtualKeyCode::X) {
+            self.renderer.view.mirror_horizontal();
+            self.update_transform();
+        }
+
+        if self.input.key_pressed(VirtualKeyCode::Y) {
+            self.renderer.view.mirror_vertical();
+            self.update_transform();
         }
 
         if self.input.key_pressed(VirtualKeyCode::H) {
-            self.renderer.pan.increase_width(1);
-            self.renderer.reconfigure_vertex_buffer();
+            self.pan(-1.0, 0.0);
         }
 
         if self.input.key_pressed(VirtualKeyCode::L) {
-            self.renderer.pan.increase_width(-1);
-            self.renderer.reconfigure_vertex_buffer();
+            self.pan(1.0, 0.0);
         }
 
         if self.input.key_pressed(VirtualKeyCode::J) {
-            self.renderer.pan.increase_height(1);
-            self.renderer.reconfigure_vertex_buffer();
+            self.pan(0.0, -1.0);
         }
 
         if self.input.key_pressed(VirtualKeyCode::K) {
-            self.renderer.pan.increase_height(-1);
-            self.renderer.reconfigure_vertex_buffer();
+            self.pan(0.0, 1.0);
+        }
+
+        if self.input.mouse_pressed(0) {
+            self.kinetic.stop();
+            self.wiping = self.near_wipe_line();
         }
 
         if self.input.mouse_held(0) {
-            let (dx, dy) = self.input.mouse_diff();
-            self.renderer.pan.increase_width(-dx as i32);
-            self.renderer.pan.increase_height(-dy as i32);
-            self.renderer.reconfigure_vertex_buffer();
+            if self.wiping {
+                if let Some((x, _)) = self.input.mouse() {
+                    let width = self.renderer.surface_size().width as f32;
+                    self.renderer.set_wipe(x / width);
+                }
+            } else {
+                let (dx, dy) = self.input.mouse_diff();
+                self.pan(dx, dy);
+                self.kinetic.drag(Vec2::new(dx, dy), Instant::now());
+            }
+        }
+
+        if self.input.mouse_released(0) {
+            if self.config.kinetic_scrolling && !self.wiping {
+                self.kinetic.release(Instant::now());
+            }
+            self.wiping = false;
+        }
+
+        if self.input.key_pressed(VirtualKeyCode::I) {
+            if self.input.held_shift() {
+                self.inspector = !self.inspector;
+            } else {
+                self.info = match self.info {
+                    Some(_) => None,
+                    None => Some(self.info_lines()),
+                };
+            }
+        }
+
+        if self.input.key_pressed(VirtualKeyCode::C) {
+            if self.input.held_control() {
+                self.copy_pixel();
+            } else {
+                // compare against the unmanaged colors
+                let mut options = self.playlist.options();
+                options.color_manage = !options.color_manage;
+                self.playlist.set_options(options);
+                for playlist in &mut self.compare {
+                    playlist.set_options(options);
+                }
+                // thumbnails are decoded again with the new options
+                self.gallery.forget_thumbnails();
+                self.renderer.thumbnails.reset();
+                self.update_image();
+                self.notify(if options.color_manage {
+                    "color management on"
+                } else {
+                    "color management off"
+                });
+            }
+        }
+
+        if self.input.key_pressed(VirtualKeyCode::E) {
+            let exposure = if self.input.held_control() {
+                0.0
+            } else if self.input.held_shift() {
+                self.renderer.exposure() - 0.5
+            } else {
+                self.renderer.exposure() + 0.5
+            };
+            self.renderer.set_exposure(exposure);
+        }
+
+        if self.input.key_pressed(VirtualKeyCode::T) {
+            self.renderer.set_tonemap(self.renderer.tonemap().next());
+        }
+
+        if self.input.key_pressed(VirtualKeyCode::F) {
+            if self.input.held_shift() {
+                let v = !self.renderer.nearest_when_magnified();
+                self.renderer.set_nearest_when_magnified(v);
+            } else {
+                self.renderer.set_filter(self.renderer.filter().next());
+            }
+        }
+
+        if self.input.key_pressed(VirtualKeyCode::P) {
+            let v = !self.renderer.pixel_perfect();
+            self.renderer.set_pixel_perfect(v);
+            if self.view_changed {
+                self.zoom(1.0, Vec2::zero());
+            } else {
+                self.apply_zoom_mode();
+            }
+        }
+
+        if self.input.key_pressed(VirtualKeyCode::A) {
+            if self.input.held_shift() {
+                self.show_adjustments = false;
+            } else if self.show_adjustments {
+                self.adjustment = self.adjustment.next();
+            } else {
+                self.show_adjustments = true;
+            }
+        }
+
+        for (key, direction) in [(VirtualKeyCode::Up, 1.0), (VirtualKeyCode::Down, -1.0)] {
+            if self.input.key_pressed(key) && self.input.held_control() {
+                let v = self.renderer.amplification() * 2f32.powf(direction);
+                self.renderer.set_amplification(v.clamp(1.0, 1024.0));
+            } else if self.input.key_pressed(key) {
+                let steps = if self.input.held_shift() { 5.0 } else { 1.0 };
+                let mut adjustments = self.renderer.adjustments();
+                adjustments.step(self.adjustment, direction * steps);
+                self.renderer.set_adjustments(adjustments);
+                self.show_adjustments = true;
+            }
+        }
+
+        if self.input.key_pressed(VirtualKeyCode::V) {
+            let mut adjustments = self.renderer.adjustments();
+            adjustments.invert = !adjustments.invert;
+            self.renderer.set_adjustments(adjustments);
+        }
+
+        if self.input.key_pressed(VirtualKeyCode::Back) {
+            self.renderer.set_adjustments(Adjustments::default());
+        }
+
+        if self.input.key_pressed(VirtualKeyCode::M) {
+            if self.input.held_shift() {
+                let v = !self.renderer.premultiplied();
+                self.renderer.set_premultiplied(v);
+            } else {
+                self.renderer.set_channels(self.renderer.channels().next());
+            }
+        }
+
+        if self.input.key_pressed(VirtualKeyCode::Z) {
+            if self.input.held_shift() {
+                self.loupe.shape = self.loupe.shape.next();
+            } else {
+                self.show_loupe = !self.show_loupe;
+            }
+        }
+
+        if self.input.key_pressed(VirtualKeyCode::B) {
+            self.renderer
+                .set_background(self.renderer.background().next());
+        }
+
+        if self.input.key_pressed(VirtualKeyCode::Space) {
+            self.animation.toggle_pause();
+        }
+
+        if self.input.key_pressed(VirtualKeyCode::Period) {
+            self.animation.step(1);
+            self.renderer.update_frame(&self.animation.current().buffer);
+        }
+
+        if self.input.key_pressed(VirtualKeyCode::Comma) {
+            self.animation.step(-1);
+            self.renderer.update_frame(&self.animation.current().buffer);
+        }
+
+        if self.input.key_pressed(VirtualKeyCode::RBracket) {
+            self.animation.set_speed(self.animation.speed() * 2.0);
+        }
+
+        if self.input.key_pressed(VirtualKeyCode::LBracket) {
+            self.animation.set_speed(self.animation.speed() / 2.0);
+        }
+
+        if self.input.key_pressed(VirtualKeyCode::Backslash) {
+            self.animation.set_speed(1.0);
+        }
+
+        if self.input.key_pressed(VirtualKeyCode::Equals)
+            || self.input.key_pressed(VirtualKeyCode::Plus)
+            || self.input.key_pressed(VirtualKeyCode::NumpadAdd)
+        {
+            self.zoom(ZOOM_STEP, Vec2::zero());
+        }
+
+        if self.input.key_pressed(VirtualKeyCode::Minus)
+            || self.input.key_pressed(VirtualKeyCode::NumpadSubtract)
+        {
+            self.zoom(ZOOM_STEP.recip(), Vec2::zero());
+        }
+
+        let scroll_diff = self.input.scroll_diff();
+        if scroll_diff.abs() >= f32::EPSILON && self.show_loupe && self.input.held_control() {
+            self.loupe.zoom = (self.loupe.zoom * ZOOM_STEP.powf(scroll_diff)).clamp(1.0, 64.0);
+        } else if scroll_diff.abs() >= f32::EPSILON {
+            let size = self.renderer.view_size();
+            let surface = Vec2::new(size.width as f32, size.height as f32);
+            let anchor = match self.input.mouse() {
+                Some((x, y)) => {
+                    let cursor = self.renderer.to_pane(Vec2::new(x, y));
+                    View::screen_to_view(cursor, surface)
+                }
+                None => Vec2::zero(),
+            };
+            self.zoom(ZOOM_STEP.powf(scroll_diff), anchor);
+        }
+    }
+
+    fn handle_gallery_input(&mut self) {
+        let surface = self.surface();
+        let page = Gallery::page_rows(surface);
+        let steps = [
+            (VirtualKeyCode::Left, -1, 0),
+            (VirtualKeyCode::Right, 1, 0),
+            (VirtualKeyCode::Up, 0, -1),
+            (VirtualKeyCode::Down, 0, 1),
+            (VirtualKeyCode::PageUp, 0, -page),
+            (VirtualKeyCode::PageDown, 0, page),
+        ];
+        for (key, columns, rows) in steps {
+            if self.input.key_pressed(key) {
+                self.gallery.step(columns, rows, surface);
+            }
+        }
+
+        if self.input.key_pressed(VirtualKeyCode::Home) {
+            self.gallery.select(0, surface);
+        }
+
+        if self.input.key_pressed(VirtualKeyCode::End) {
+            self.gallery.select(usize::MAX, surface);
+        }
+
+        if self.input.key_pressed(VirtualKeyCode::Return)
+            || self.input.key_pressed(VirtualKeyCode::NumpadEnter)
+        {
+            self.open_selected();
+        }
+
+        if self.input.key_pressed(VirtualKeyCode::Escape) {
+            self.renderer.set_gallery(false);
         }
 
         let scroll_diff = self.input.scroll_diff();
         if scroll_diff.abs() >= f32::EPSILON {
-            // todo: multiply a factor set by user
-            self.renderer.add_scale(scroll_diff * 0.1);
+            self.gallery.scroll_rows(-scroll_diff, surface);
+        }
+
+        if self.input.mouse_pressed(0) {
+            let cursor = self.input.mouse().map(|(x, y)| Vec2::new(x, y));
+            match cursor.and_then(|p| self.gallery.index_at(p, surface)) {
+                // a second click opens
+                Some(index) if index == self.gallery.selected() => self.open_selected(),
+                Some(index) => self.gallery.select(index, surface),
+                None => {}
+            }
+        }
+    }
+
+    fn toggle_gallery(&mut self) {
+        let show = !self.renderer.gallery();
+        self.renderer.set_gallery(show);
+        if !show {
+            return;
+        }
+        let surface = self.surface();
+        let current = self.playlist.index().unwrap_or(0);
+        self.gallery.select(current, surface);
+        if self.gallery.thumbnailer.is_none() {
+            // items around the current one first
+            let mut jobs: Vec<_> = (0..self.playlist.len())
+                .filter_map(|i| Some((i, self.playlist.path(i)?.to_owned())))
+                .collect();
+            jobs.sort_by_key(|(i, _)| i.abs_diff(current));
+            self.gallery.thumbnailer = Some(Thumbnailer::new(
+                jobs,
+                self.playlist.options(),
+                self.playlist.auto_orient(),
+            ));
         }
     }
 
+    fn open_selected(&mut self) {
+        self.playlist.set_index(self.gallery.selected());
+        self.renderer.set_gallery(false);
+        self.update_image();
+    }
+
+    /// Uploads finished thumbnails and lays out the visible ones.
+    fn update_gallery(&mut self) {
+        if let Some(thumbnailer) = &mut self.gallery.thumbnailer {
+            for (index, img) in thumbnailer.ready() {
+                if let Some(img) = img {
+                    let renderer = &mut self.renderer;
+                    let evicted =
+                        renderer
+                            .thumbnails
+                            .insert(&renderer.device, &renderer.queue, index, &img);
+                    if let Some(evicted) = evicted {
+                        self.gallery.evict(evicted);
+                    }
+                }
+            }
+        }
+        if !self.renderer.gallery() {
+            return;
+        }
+        let surface = self.surface();
+        let visible = self.gallery.visible(surface);
+        self.gallery.restore(visible.clone());
+        self.gallery.prioritize(visible.clone());
+        let size = thumbnail::SIZE as f32;
+        let thumbnails = &mut self.renderer.thumbnails;
+        thumbnails.clear();
+        let selected = self.gallery.cell(self.gallery.selected(), surface);
+        thumbnails.rect(
+            [selected.x - 4.0, selected.y - 4.0],
+            [size + 8.0; 2],
+            SELECTION_COLOR,
+        );
+        for index in visible {
+            let cell = self.gallery.cell(index, surface);
+            if !thumbnails.thumbnail(index, [cell.x, cell.y], size) {
+                thumbnails.rect([cell.x, cell.y], [size; 2], PLACEHOLDER_COLOR);
+            }
+        }
+    }
+
+    fn surface(&self) -> Vec2 {
+        let size = self.renderer.surface_size();
+        Vec2::new(size.width as f32, size.height as f32)
+    }
+
+    /// Shows the current item. Every change of position ends up here, which moves the
+    /// `--compare` playlists along so all panes show the same inputs.
     fn update_image(&mut self) {
-        let img = match self.playlist.current_image() {
-            Ok(img) => img,
-            _ => Rgba8Image::new(1, 1),
+        for playlist in &mut self.compare {
+            playlist.follow(&self.playlist);
+        }
+        let frames = match self.playlist.current_frames() {
+            Ok(frames) if !frames.is_empty() => frames,
+            _ => vec![Frame::still(Rgba8Image::new(1, 1).into())],
+        };
+        // keep the playback speed the user picked for the previous image
+        self.animation = Animation::new(frames, self.animation.speed());
+        self.kinetic.stop();
+        let (width, height) = self.animation.current().buffer.dimensions();
+        self.renderer.view = View::centered(Vec2::new(width as f32, height as f32));
+        self.renderer.update_image(&self.animation.current().buffer);
+        self.update_compare();
+        self.apply_zoom_mode();
+        self.update_title();
+        self.check_precision();
+        if self.info.is_some() {
+            self.info = Some(self.info_lines());
+        }
+    }
+
+    /// Tells the user when the GPU can't show every bit of the image.
+    fn check_precision(&mut self) {
+        if self
+            .renderer
+            .loses_precision(&self.animation.current().buffer)
+        {
+            self.notify("16 bits reduced to half float precision");
+        }
+    }
+
+    /// Image pixel under the cursor and its value, if it is inside the image.
+    fn inspect(&self) -> Option<((i64, i64), Option<Sample>)> {
+        let (x, y) = self.input.mouse()?;
+        let cursor = Vec2::new(x, y);
+        let size = self.renderer.view_size();
+        let surface = Vec2::new(size.width as f32, size.height as f32);
+        let p = self
+            .renderer
+            .shown_view()
+            .screen_to_image(self.renderer.to_pane(cursor), surface);
+        let frame = match self.renderer.pane_at(cursor) {
+            0 => Some(self.animation.current()),
+            i => self.compare_images.get(i - 1).and_then(Option::as_ref),
+        };
+        let (x, y) = (p.x.floor() as i64, p.y.floor() as i64);
+        let texel = match (frame, u32::try_from(x), u32::try_from(y)) {
+            (Some(frame), Ok(x), Ok(y)) => frame.buffer.texel(x, y).map(|texel| Sample {
+                texel,
+                converted: frame.converted,
+            }),
+            _ => None,
+        };
+        Some(((x, y), texel))
+    }
+
+    /// Prints the pixel under the cursor to stdout, for scripts reading the output. The last
+    /// field tells whether the values are the stored ones or were converted for display.
+    fn copy_pixel(&mut self) {
+        match self.inspect() {
+            Some(((x, y), Some(Sample { texel, converted }))) => println!(
+                "{} {},{} {} {} {} {}",
+                self.playlist.current_name().unwrap_or_default(),
+                x,
+                y,
+                texel.raw(),
+                texel.hex(),
+                floats(texel),
+                if converted { "display" } else { "file" }
+            ),
+            _ => self.notify("no pixel under the cursor"),
+        }
+    }
+
+    fn inspector_lines(&self) -> Vec<String> {
+        match self.inspect() {
+            Some(((x, y), Some(Sample { texel, converted }))) => vec![
+                format!("x {} y {}", x, y),
+                format!("rgba  {}", texel.raw()),
+                format!("hex   {}", texel.hex()),
+                format!("float {}", floats(texel)),
+                // converted values are what the screen shows, not what the file stores
+                if converted {
+                    "display sRGB".to_owned()
+                } else {
+                    "as stored".to_owned()
+                },
+            ],
+            Some(((x, y), None)) => vec![format!("x {} y {}", x, y), "outside".to_owned()],
+            None => vec!["no cursor".to_owned()],
+        }
+    }
+
+    /// Moves every playlist to another item.
+    fn advance(&mut self, d: isize) {
+        self.playlist.advance(d);
+        self.update_image();
+        if self.playlist.index().is_none() && !self.playlist.is_empty() {
+            self.notify(if d > 0 {
+                "end of list"
+            } else {
+                "start of list"
+            });
+        }
+    }
+
+    /// Shows `message` for a moment.
+    fn notify(&mut self, message: impl Into<String>) {
+        self.message = Some((message.into(), Instant::now() + MESSAGE_TIME));
+    }
+
+    fn hud_lines(&self) -> Vec<String> {
+        let (width, height) = self.animation.current().buffer.dimensions();
+        vec![format!(
+            "{}  {}x{}  {}%",
+            self.hud_title,
+            width,
+            height,
+            (self.renderer.view.scale * 100.0).round()
+        )]
+    }
+
+    /// Loads the images shown next to the current one: the current items of the
+    /// `--compare` playlists, or else the items following it in the playlist.
+    fn update_compare(&mut self) {
+        // comparisons and flicker need a second image even without panes
+        let panes = if self.renderer.comparison().is_some() || self.renderer.flicker() {
+            self.panes.max(2)
+        } else {
+            self.panes
+        };
+        self.compare_images = (1..panes)
+            .map(|i| {
+                let frames = match self.compare.get(i - 1) {
+                    Some(playlist) => playlist.current_frames(),
+                    None => self.playlist.frames_after(i),
+                };
+                frames.ok()?.into_iter().next()
+            })
+            .collect();
+        // a missing image leaves its pane empty rather than moving the others
+        let placeholder = Rgba8Image::new(1, 1).into();
+        let images: Vec<_> = self
+            .compare_images
+            .iter()
+            .map(|frame| frame.as_ref().map_or(&placeholder, |frame| &frame.buffer))
+            .collect();
+        self.renderer.set_compare(&images);
+    }
+
+    /// Draws the first two images on top of each other with `comparison`, or side by side
+    /// again if they already are.
+    fn toggle_comparison(&mut self, comparison: Comparison) {
+        let v = match self.renderer.comparison() {
+            Some(current) if current == comparison => None,
+            _ => Some(comparison),
+        };
+        self.renderer.set_comparison(v);
+        self.update_compare();
+        self.update_transform();
+    }
+
+    fn near_wipe_line(&self) -> bool {
+        match (self.renderer.comparison(), self.input.mouse()) {
+            (Some(Comparison::Wipe), Some((x, _))) => {
+                let width = self.renderer.surface_size().width as f32;
+                (x - self.renderer.wipe() * width).abs() <= 8.0
+            }
+            _ => false,
+        }
+    }
+
+    fn info_lines(&self) -> Vec<String> {
+        match self.playlist.current_metadata() {
+            Ok(metadata) => metadata.lines(),
+            Err(e) => vec![e.to_string()],
+        }
+    }
+
+    fn update_overlay(&mut self) {
+        if self.renderer.gallery() {
+            let selected = self.gallery.selected();
+            let line = match self.playlist.name(selected) {
+                Some(name) => format!("{} ({}/{})", name, selected + 1, self.playlist.len()),
+                None => "no images".to_owned(),
+            };
+            let text = &mut self.renderer.text;
+            text.clear();
+            let margin = text.cell()[1];
+            let size = text.measure(&[&line]);
+            let height = self.renderer.surface_size().height as f32;
+            self.renderer
+                .text
+                .panel([margin, height - margin - size[1]], &[line]);
+            return;
+        }
+        if matches!(&self.message, Some((_, until)) if *until <= Instant::now()) {
+            self.message = None;
+        }
+        let adjustments = self.renderer.adjustments();
+        let hud = (self.show_hud && !self.playlist.is_empty()).then(|| self.hud_lines());
+        let inspector = self.inspector.then(|| self.inspector_lines());
+        let difference = (self.renderer.comparison() == Some(Comparison::Difference))
+            .then(|| vec![format!("difference x{}", self.renderer.amplification())]);
+        let surface = self.renderer.surface_size();
+        let (width, height) = (surface.width as f32, surface.height as f32);
+        let text = &mut self.renderer.text;
+        text.clear();
+        let margin = text.cell()[1];
+        let mut top = margin;
+        if let Some(lines) = hud {
+            text.panel([margin, top], &lines);
+            top += text.measure(&lines)[1] + margin / 2.0;
+        }
+        if let Some(info) = &self.info {
+            text.panel([margin, top], info);
+        }
+        if self.show_adjustments || adjustments != Adjustments::default() {
+            let lines = adjustments.lines(self.adjustment);
+            let size = text.measure(&lines);
+            text.panel([margin, height - margin - size[1]], &lines);
+        }
+        if let Some(lines) = inspector {
+            let size = text.measure(&lines);
+            text.panel([width - margin - size[0], margin], &lines);
+        }
+        if let Some(lines) = difference {
+            let size = text.measure(&lines);
+            text.panel(
+                [width - margin - size[0], height - margin - size[1]],
+                &lines,
+            );
+        }
+        if let Some((message, _)) = &self.message {
+            let lines = [message];
+            let size = text.measure(&lines);
+            // above the panels in the bottom corners
+            let bottom = height - margin * 4.0 - size[1];
+            text.panel([((width - size[0]) / 2.0).round(), bottom], &lines);
+        }
+    }
+
+    fn advance_page(&mut self, d: isize) {
+        match self.playlist.advance_page(d) {
+            Ok(true) => self.update_image(),
+            Ok(false) => {}
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+
+    fn update_title(&mut self) {
+        let name = self.playlist.current_name();
+        let page = match self.playlist.pages() {
+            Ok(pages) if pages > 1 => format!(" [{}/{}]", self.playlist.page() + 1, pages),
+            _ => String::new(),
+        };
+        let title = match &name {
+            Some(name) => format!("{}{} - yume", name, page),
+            None => "yume".to_owned(),
+        };
+        self.window.set_title(&title);
+        let position = match self.playlist.index() {
+            Some(index) => format!("{}/{}", index + 1, self.playlist.len()),
+            None => format!("-/{}", self.playlist.len()),
+        };
+        self.hud_title = format!("{}{}  {}", name.unwrap_or_default(), page, position);
+    }
+
+    fn update_animation(&mut self) {
+        if self.animation.tick(Instant::now()) {
+            self.renderer.update_frame(&self.animation.current().buffer);
+        }
+    }
+
+    fn update_kinetic(&mut self) {
+        if let Some(delta) = self.kinetic.tick(Instant::now()) {
+            let before = self.renderer.view.center;
+            self.pan(delta.x, delta.y);
+            // stop at the edge of the image
+            if self.renderer.view.center == before {
+                self.kinetic.stop();
+            }
+        }
+    }
+
+    fn update_loupe(&mut self) {
+        let loupe = match self.input.mouse() {
+            Some((x, y)) if self.show_loupe => Some(Loupe {
+                center: Vec2::new(x, y),
+                ..self.loupe
+            }),
+            _ => None,
         };
-        self.renderer.pan = Pan::default();
-        self.renderer.update_image(&img);
-        self.renderer.set_scale(self.scale_to_fit().min(1f32))
+        if loupe != self.renderer.loupe() {
+            self.renderer.set_loupe(loupe);
+        }
+    }
+
+    /// Moves the image by a distance in screen pixels.
+    fn pan(&mut self, dx: f32, dy: f32) {
+        self.renderer.view.pan(Vec2::new(dx, dy));
+        self.renderer.update_view();
+        self.view_changed = true;
     }
 
-    fn scale_to_fit(&self) -> f32 {
-        let src = self.renderer.surface_size();
-        let dst = self.renderer.texture_size();
+    /// Multiplies the scale by `factor` within the configured limits,
+    /// keeping the image point at `anchor` (view coordinates) in place.
+    fn zoom(&mut self, factor: f32, anchor: Vec2) {
+        let current = self.renderer.view.scale;
+        let pixel_perfect = self.renderer.pixel_perfect();
+        let mut scale = if pixel_perfect {
+            let steps = match factor {
+                f if f > 1.0 => 1,
+                f if f < 1.0 => -1,
+                _ => 0,
+            };
+            zoom::step(current, steps)
+        } else {
+            current * factor
+        };
+        // a fitted image may already be outside the limits, never zoom further out of them
+        let (min, max) = (self.config.min_zoom, self.config.max_zoom);
+        scale = scale.clamp(min.min(current), max.max(current));
+        if pixel_perfect {
+            scale = zoom::snap(scale);
+        }
+        self.renderer.view.zoom_at(scale, anchor);
+        if self.zoom_mode == ZoomMode::Locked {
+            self.locked_zoom = scale;
+        }
+        self.renderer.update_view();
+        self.view_changed = true;
+    }
 
-        (src.width as f32 / dst.width as f32).min(src.height as f32 / dst.height as f32)
+    /// Switches to `mode` and applies it; locking keeps the current scale.
+    fn set_zoom_mode(&mut self, mode: ZoomMode) {
+        if mode == ZoomMode::Locked {
+            self.locked_zoom = self.renderer.view.scale;
+        }
+        self.zoom_mode = mode;
+        self.apply_zoom_mode();
     }
 
-    pub fn handle_scale_to_fit(&mut self) {
-        self.renderer.set_scale(self.scale_to_fit());
+    /// Centers the image at the scale of the zoom mode, dropping any zooming or panning.
+    fn apply_zoom_mode(&mut self) {
+        let surface = self.renderer.view_size();
+        let size = self.renderer.texture_size();
+        let size = Vec2::new(size.width as f32, size.height as f32);
+        let bounds = self.renderer.view.bounds(size);
+        let mut scale = self.zoom_mode.scale(
+            bounds,
+            Vec2::new(surface.width as f32, surface.height as f32),
+            self.locked_zoom,
+        );
+        if self.renderer.pixel_perfect() {
+            scale = zoom::snap(scale);
+        }
+        self.renderer.view.center = size / 2.0;
+        self.renderer.set_scale(scale);
+        self.view_changed = false;
     }
+
+    /// Refits the image after rotating or flipping it, unless the user moved the view.
+    fn update_transform(&mut self) {
+        if self.view_changed {
+            self.renderer.update_view();
+        } else {
+            self.apply_zoom_mode();
+        }
+    }
+
+    /// Writes the current rotation and flips into the file, then shows it as stored.
+    fn save_orientation(&mut self) {
+        let view = self.renderer.view;
+        let turns = view.rotation / FRAC_PI_2;
+        if (turns - turns.round()).abs() > 1e-3 {
+            self.notify("only quarter turns can be saved");
+            return;
+        }
+        // a vertical flip is a horizontal one turned upside down
+        let (turns, flip) = match (view.flip_horizontal, view.flip_vertical) {
+            (false, false) => (turns.round() as u8, false),
+            (true, false) => (turns.round() as u8, true),
+            (false, true) => (turns.round() as u8 + 2, true),
+            (true, true) => (turns.round() as u8 + 2, false),
+        };
+        match self
+            .playlist
+            .save_orientation(Orientation::from_parts(turns, flip))
+        {
+            Ok(()) => {
+                self.update_image();
+                self.notify("orientation saved");
+            }
+            Err(e) => self.notify(e.to_string()),
+        }
+    }
+}
+
+/// A pixel value read from an image.
+struct Sample {
+    texel: Texel,
+    /// Whether the value was converted to sRGB for display rather than read from the file.
+    converted: bool,
+}
+
+fn floats(texel: Texel) -> String {
+    let channels: Vec<String> = texel.to_f32().iter().map(|v| format!("{:.3}", v)).collect();
+    channels.join(" ")
 }
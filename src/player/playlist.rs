@@ -1,12 +1,17 @@
+pub mod color;
 pub mod handler;
+pub mod metadata;
+pub mod orientation;
+pub mod pages;
 
 use std::path::{Path, PathBuf};
 use walkdir::{Error, WalkDir};
 
-pub use handler::Playlist;
+pub use handler::{DecodeOptions, Frame, Playlist};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub enum Pos {
+    #[default]
     Start,
     Normal(usize),
     End,
@@ -20,7 +25,7 @@ impl Pos {
     pub fn advance(&mut self, d: isize, len: usize) {
         match self {
             Self::Start if d > 0 => *self = Self::Normal(d as usize - 1),
-            Self::End if d < 0 => *self = Self::Normal((len as isize - d) as usize),
+            Self::End if d < 0 => *self = Self::Normal((len as isize + d).max(0) as usize),
             Self::Normal(pos) => {
                 let new_pos = *pos as isize + d;
                 *self = if new_pos < 0 {
@@ -42,12 +47,6 @@ impl Pos {
     }
 }
 
-impl Default for Pos {
-    fn default() -> Self {
-        Self::Start
-    }
-}
-
 pub fn read_dir(path: &Path, sources: &mut Vec<PathBuf>) -> Result<(), Error> {
     for entry in WalkDir::new(path) {
         let entry = entry?;
@@ -57,3 +56,29 @@ pub fn read_dir(path: &Path, sources: &mut Vec<PathBuf>) -> Result<(), Error> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn advance_stops_past_either_end() {
+        let mut pos = Pos::Normal(1);
+        pos.advance(1, 3);
+        assert_eq!(pos, Pos::Normal(2));
+        pos.advance(1, 3);
+        assert_eq!(pos, Pos::End);
+        pos.advance(1, 3);
+        assert_eq!(pos, Pos::End);
+        pos.advance(-1, 3);
+        assert_eq!(pos, Pos::Normal(2));
+
+        let mut pos = Pos::Normal(0);
+        pos.advance(-1, 3);
+        assert_eq!(pos, Pos::Start);
+        pos.advance(-1, 3);
+        assert_eq!(pos, Pos::Start);
+        pos.advance(1, 3);
+        assert_eq!(pos, Pos::Normal(0));
+    }
+}
@@ -0,0 +1,281 @@
+use crate::{
+    player::renderer::texture::{srgb_to_linear, Rgba32FImage},
+    Pixels, Rgba8Image,
+};
+use image::{
+    codecs::{jpeg::JpegDecoder, png::PngDecoder, tiff::TiffDecoder, webp::WebPDecoder},
+    io::Reader,
+    DynamicImage, ImageBuffer, ImageDecoder, ImageFormat,
+};
+use qcms::{DataType, Intent, Profile, Transform};
+use std::{fs::File, io::BufReader, path::Path};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// The color space signature stored at bytes 16..20 of an ICC profile header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ColorSpace {
+    Rgb,
+    Gray,
+    Cmyk,
+    Other,
+}
+
+impl ColorSpace {
+    fn of(icc: &[u8]) -> Self {
+        match icc.get(16..20) {
+            Some(b"RGB ") => Self::Rgb,
+            Some(b"GRAY") => Self::Gray,
+            Some(b"CMYK") => Self::Cmyk,
+            _ => Self::Other,
+        }
+    }
+}
+
+/// Reads the embedded ICC profile of `path`, if its format can carry one.
+pub fn icc_profile(path: &Path) -> Result<Option<Vec<u8>>> {
+    let reader = Reader::open(path)?.with_guessed_format()?;
+    Ok(match reader.format() {
+        Some(ImageFormat::Png) => PngDecoder::new(reader.into_inner())?.icc_profile(),
+        Some(ImageFormat::Jpeg) => JpegDecoder::new(reader.into_inner())?.icc_profile(),
+        Some(ImageFormat::WebP) => WebPDecoder::new(reader.into_inner())?.icc_profile(),
+        Some(ImageFormat::Tiff) => TiffDecoder::new(reader.into_inner())?.icc_profile(),
+        _ => None,
+    })
+}
+
+fn parse(icc: &[u8]) -> Result<Box<Profile>> {
+    Profile::new_from_slice(icc, false).ok_or_else(|| "invalid ICC profile".into())
+}
+
+/// Converts pixels described by an RGB or gray profile to sRGB in place. Float images with
+/// values outside 0 to 1 are left unchanged and reported as an error.
+pub fn to_srgb(icc: &[u8], img: &mut Pixels) -> Result<()> {
+    let input = parse(icc)?;
+    if input.is_sRGB() {
+        return Ok(());
+    }
+    let space = ColorSpace::of(icc);
+    let lattice = match img {
+        Pixels::Rgba8(img) => return convert(&input, space, img),
+        // the lattice only covers 0 to 1, so high dynamic range values would be clipped
+        Pixels::Rgba32F(img) if out_of_range(img) => {
+            return Err("ICC profile not applied to values outside 0 to 1".into());
+        }
+        _ => Lattice::new(&input, space)?,
+    };
+    match img {
+        Pixels::Rgba8(_) => {}
+        Pixels::Rgba16(img) => {
+            for p in img.pixels_mut() {
+                let rgb = lattice.get([p[0], p[1], p[2]].map(|v| v as f32 / u16::MAX as f32));
+                for (c, v) in p.0.iter_mut().zip(rgb) {
+                    *c = (v * u16::MAX as f32).round() as u16;
+                }
+            }
+        }
+        Pixels::Rgba32F(img) => {
+            // the stored values are encoded as the profile describes, decoded to linear light after
+            for p in img.pixels_mut() {
+                let rgb = lattice.get([p[0], p[1], p[2]]);
+                for (c, v) in p.0.iter_mut().zip(rgb) {
+                    *c = srgb_to_linear(v);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether any color channel of `img` is below 0 or above 1.
+fn out_of_range(img: &Rgba32FImage) -> bool {
+    img.pixels()
+        .any(|p| p.0[..3].iter().any(|v| !(0.0..=1.0).contains(v)))
+}
+
+fn output() -> Box<Profile> {
+    let mut output = Profile::new_sRGB();
+    output.precache_output_transform();
+    output
+}
+
+/// Converts 8-bit pixels in place.
+fn convert(input: &Profile, space: ColorSpace, img: &mut Rgba8Image) -> Result<()> {
+    let output = output();
+    match space {
+        ColorSpace::Rgb => {
+            let transform = Transform::new(input, &output, DataType::RGBA8, Intent::default())
+                .ok_or("unsupported ICC profile")?;
+            transform.apply(img);
+        }
+        ColorSpace::Gray => {
+            let transform = Transform::new_to(
+                input,
+                &output,
+                DataType::GrayA8,
+                DataType::RGBA8,
+                Intent::default(),
+            )
+            .ok_or("unsupported ICC profile")?;
+            let gray: Vec<u8> = img.pixels().flat_map(|p| [p[0], p[3]]).collect();
+            transform.convert(&gray, img);
+        }
+        space => return Err(format!("{:?} profiles can't be applied to RGB data", space).into()),
+    }
+    Ok(())
+}
+
+/// sRGB colors of evenly spaced 8-bit inputs, for pixels with more than 8 bits per channel.
+///
+/// qcms only converts 8-bit data, so deeper pixels are interpolated between the converted
+/// colors around them instead of being rounded to 8 bits first.
+struct Lattice {
+    /// Points along each axis, only the first one for gray profiles.
+    points: usize,
+    gray: bool,
+    colors: Vec<[f32; 3]>,
+}
+
+impl Lattice {
+    /// Distance between neighbouring points in 8-bit steps.
+    const SPACING: usize = 5;
+
+    fn new(input: &Profile, space: ColorSpace) -> Result<Self> {
+        // other color spaces are turned down by the conversion below
+        let gray = space == ColorSpace::Gray;
+        let points = 255 / Self::SPACING + 1;
+        let axis = |i: usize| (i * Self::SPACING) as u8;
+        let inputs: Vec<[u8; 4]> = if gray {
+            (0..points)
+                .map(|i| [axis(i), axis(i), axis(i), 255])
+                .collect()
+        } else {
+            (0..points.pow(3))
+                .map(|i| {
+                    let (r, g, b) = (i / (points * points), i / points % points, i % points);
+                    [axis(r), axis(g), axis(b), 255]
+                })
+                .collect()
+        };
+        let mut img = Rgba8Image::from_raw(inputs.len() as u32, 1, inputs.concat())
+            .ok_or("invalid lattice")?;
+        convert(input, space, &mut img)?;
+        let colors = img
+            .pixels()
+            .map(|p| [p[0], p[1], p[2]].map(|v| v as f32 / u8::MAX as f32))
+            .collect();
+        Ok(Self {
+            points,
+            gray,
+            colors,
+        })
+    }
+
+    /// The converted color of `rgb`, with channels from 0 to 1.
+    fn get(&self, rgb: [f32; 3]) -> [f32; 3] {
+        // lower point and weight of the upper one on each axis
+        let split = |v: f32| {
+            let x = v.clamp(0.0, 1.0) * (self.points - 1) as f32;
+            let i = (x as usize).min(self.points - 2);
+            (i, x - i as f32)
+        };
+        let mix = |a: [f32; 3], b: [f32; 3], t: f32| [0, 1, 2].map(|c| a[c] + (b[c] - a[c]) * t);
+        if self.gray {
+            let (i, t) = split(rgb[0]);
+            return mix(self.colors[i], self.colors[i + 1], t);
+        }
+
+        let [(r, tr), (g, tg), (b, tb)] = rgb.map(split);
+        let at =
+            |r: usize, g: usize, b: usize| self.colors[(r * self.points + g) * self.points + b];
+        let plane = |r: usize| {
+            mix(
+                mix(at(r, g, b), at(r, g, b + 1), tb),
+                mix(at(r, g + 1, b), at(r, g + 1, b + 1), tb),
+                tg,
+            )
+        };
+        mix(plane(r), plane(r + 1), tr)
+    }
+}
+
+/// Decodes a CMYK JPEG through its profile.
+///
+/// `image` flattens CMYK to RGB without looking at the profile, so the raw ink values have to be
+/// read again. Returns `None` for JPEGs that aren't CMYK or whose profile isn't a CMYK one.
+pub fn decode_cmyk_jpeg(path: &Path, icc: &[u8]) -> Result<Option<Rgba8Image>> {
+    if ColorSpace::of(icc) != ColorSpace::Cmyk {
+        return Ok(None);
+    }
+    let mut decoder = jpeg_decoder::Decoder::new(BufReader::new(File::open(path)?));
+    let cmyk = decoder.decode()?;
+    let info = decoder.info().ok_or("missing JPEG header")?;
+    if info.pixel_format != jpeg_decoder::PixelFormat::CMYK32 {
+        return Ok(None);
+    }
+
+    let input = parse(icc)?;
+    let output = output();
+    let transform = Transform::new_to(
+        &input,
+        &output,
+        DataType::CMYK,
+        DataType::RGB8,
+        Intent::default(),
+    )
+    .ok_or("unsupported ICC profile")?;
+    let mut rgb = vec![0; cmyk.len() / 4 * 3];
+    transform.convert(&cmyk, &mut rgb);
+
+    let (width, height) = (info.width as u32, info.height as u32);
+    let rgb = ImageBuffer::from_raw(width, height, rgb).ok_or("truncated JPEG data")?;
+    Ok(Some(DynamicImage::ImageRgb8(rgb).to_rgba8()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lattice_interpolates_between_points() {
+        // a profile that changes nothing but isn't recognized as sRGB
+        let identity = Lattice {
+            points: 3,
+            gray: false,
+            colors: (0..27)
+                .map(|i| [i / 9, i / 3 % 3, i % 3].map(|v| v as f32 / 2.0))
+                .collect(),
+        };
+        for rgb in [[0.0, 0.0, 0.0], [0.1, 0.5, 0.9], [1.0, 0.3, 0.0], [1.0; 3]] {
+            let out = identity.get(rgb);
+            for c in 0..3 {
+                assert!((out[c] - rgb[c]).abs() < 1e-6, "{:?} -> {:?}", rgb, out);
+            }
+        }
+        // out of range values are clamped
+        assert_eq!(identity.get([-1.0, 2.0, 0.5]), [0.0, 1.0, 0.5]);
+    }
+
+    #[test]
+    fn finds_high_dynamic_range_values() {
+        let mut img = Rgba32FImage::from_pixel(2, 1, image::Rgba([0.0, 0.5, 1.0, 1.0]));
+        assert!(!out_of_range(&img));
+        img.get_pixel_mut(1, 0)[1] = 4.0;
+        assert!(out_of_range(&img));
+        img.get_pixel_mut(1, 0)[1] = -0.1;
+        assert!(out_of_range(&img));
+        // alpha doesn't go through the profile
+        let img = Rgba32FImage::from_pixel(1, 1, image::Rgba([0.5, 0.5, 0.5, 2.0]));
+        assert!(!out_of_range(&img));
+    }
+
+    #[test]
+    fn deep_pixels_match_8_bit_conversion() {
+        let input = Profile::new_gray_with_gamma(1.0);
+        let lattice = Lattice::new(&input, ColorSpace::Gray).unwrap();
+        let mut img = Rgba8Image::from_pixel(1, 1, image::Rgba([102, 102, 102, 255]));
+        convert(&input, ColorSpace::Gray, &mut img).unwrap();
+        let expected = img.get_pixel(0, 0)[0] as f32 / 255.0;
+        assert!((lattice.get([102.0 / 255.0; 3])[0] - expected).abs() < 1.0 / 255.0);
+    }
+}
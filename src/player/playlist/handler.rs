@@ -1,8 +1,16 @@
-use super::Pos;
-use crate::Rgba8Image;
+use super::{color, metadata::Metadata, orientation::Orientation, pages::Container, Pos};
+use crate::Pixels;
+use image::{
+    codecs::{gif::GifDecoder, png::PngDecoder, webp::WebPDecoder},
+    io::Reader,
+    AnimationDecoder, ImageFormat,
+};
 use std::{
+    cell::RefCell,
+    collections::HashMap,
     fmt::Debug,
     path::{Path, PathBuf},
+    time::Duration,
 };
 use walkdir::WalkDir;
 
@@ -12,6 +20,54 @@ type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 pub struct Playlist {
     pub(crate) items: Vec<Box<dyn Handler>>,
     pub(crate) pos: Pos,
+    pub(crate) page: usize,
+    pub(crate) auto_orient: bool,
+    pub(crate) options: DecodeOptions,
+    /// Page counts by item, as counting can mean reading through the whole file.
+    page_counts: RefCell<HashMap<usize, usize>>,
+}
+
+/// Settings that change how an item is decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeOptions {
+    /// Convert images with an embedded ICC profile to sRGB.
+    pub color_manage: bool,
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        Self { color_manage: true }
+    }
+}
+
+/// A single decoded frame. Still images are made of one frame without delay.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub buffer: Pixels,
+    pub delay: Duration,
+    /// Whether `buffer` was converted from the file's color profile to sRGB, so it holds
+    /// display values rather than the stored ones.
+    pub converted: bool,
+}
+
+impl Frame {
+    pub fn still(buffer: Pixels) -> Self {
+        Self {
+            buffer,
+            delay: Duration::ZERO,
+            converted: false,
+        }
+    }
+}
+
+impl From<image::Frame> for Frame {
+    fn from(frame: image::Frame) -> Self {
+        Self {
+            delay: frame.delay().into(),
+            buffer: frame.into_buffer().into(),
+            converted: false,
+        }
+    }
 }
 
 impl Playlist {
@@ -25,42 +81,320 @@ impl Playlist {
         } else {
             Pos::Normal(0)
         };
-        Self { items, pos }
+        Self {
+            items,
+            pos,
+            page: 0,
+            auto_orient: true,
+            options: DecodeOptions::default(),
+            page_counts: RefCell::default(),
+        }
     }
 
     fn current(&self) -> Option<&dyn Handler> {
         self.items.get(self.pos.to_index()?).map(AsRef::as_ref)
     }
 
-    pub fn current_image(&self) -> Result<Rgba8Image> {
-        self.current().ok_or("no image")?.handle()
+    pub fn current_frames(&self) -> Result<Vec<Frame>> {
+        self.frames(self.current().ok_or("no image")?, self.page)
+    }
+
+    /// Frames of the first page of the item `offset` places after the current one.
+    pub fn frames_after(&self, offset: usize) -> Result<Vec<Frame>> {
+        let index = self.pos.to_index().ok_or("no image")? + offset;
+        let item = self.items.get(index).ok_or("no image")?;
+        self.frames(item.as_ref(), 0)
+    }
+
+    fn frames(&self, item: &dyn Handler, page: usize) -> Result<Vec<Frame>> {
+        decode_item(item, page, self.options, self.auto_orient)
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Index of the current item.
+    pub fn index(&self) -> Option<usize> {
+        self.pos.to_index()
+    }
+
+    /// Makes the item at `index` current.
+    pub fn set_index(&mut self, index: usize) {
+        if index < self.items.len() {
+            self.pos = Pos::Normal(index);
+            self.page = 0;
+        }
+    }
+
+    /// Moves to the item and page `other` is at, whether or not this playlist has them.
+    pub fn follow(&mut self, other: &Playlist) {
+        self.pos = other.pos;
+        self.page = other.page;
+    }
+
+    pub fn name(&self, index: usize) -> Option<String> {
+        self.items.get(index).map(|item| item.name())
+    }
+
+    /// The file of the item at `index`, for items that are files.
+    pub fn path(&self, index: usize) -> Option<&Path> {
+        self.items.get(index)?.path()
+    }
+
+    /// Whether EXIF orientation is applied to decoded images.
+    pub fn auto_orient(&self) -> bool {
+        self.auto_orient
+    }
+
+    /// Sets whether EXIF orientation is applied to decoded images.
+    pub fn set_auto_orient(&mut self, auto_orient: bool) {
+        self.auto_orient = auto_orient;
+    }
+
+    pub fn options(&self) -> DecodeOptions {
+        self.options
+    }
+
+    /// Changes how items are decoded, effective from the next `current_frames`.
+    pub fn set_options(&mut self, options: DecodeOptions) {
+        self.options = options;
+    }
+
+    /// Makes `view` part of the stored orientation of the current item, where `view` is what
+    /// has been done to it on top of the orientation it is shown with.
+    pub fn save_orientation(&self, view: Orientation) -> Result<()> {
+        let current = self.current().ok_or("no image")?;
+        let shown = if self.auto_orient {
+            current.orientation()
+        } else {
+            Orientation::Normal
+        };
+        current.set_orientation(shown.then(view))
+    }
+
+    pub fn current_metadata(&self) -> Result<Metadata> {
+        self.current().ok_or("no image")?.metadata()
+    }
+
+    pub fn current_name(&self) -> Option<String> {
+        self.current().map(Handler::name)
+    }
+
+    /// Number of pages of the current item.
+    pub fn pages(&self) -> Result<usize> {
+        let index = self.pos.to_index().ok_or("no image")?;
+        if let Some(&pages) = self.page_counts.borrow().get(&index) {
+            return Ok(pages);
+        }
+        let pages = self.items[index].pages()?;
+        self.page_counts.borrow_mut().insert(index, pages);
+        Ok(pages)
+    }
+
+    pub fn page(&self) -> usize {
+        self.page
     }
 
     pub fn advance(&mut self, d: isize) {
-        self.pos.advance(d, self.items.len())
+        self.pos.advance(d, self.items.len());
+        self.page = 0;
+    }
+
+    /// Moves between pages of the current item, returning whether the page changed.
+    pub fn advance_page(&mut self, d: isize) -> Result<bool> {
+        let last = self.pages()?.saturating_sub(1) as isize;
+        let page = (self.page as isize + d).clamp(0, last) as usize;
+        let changed = page != self.page;
+        self.page = page;
+        Ok(changed)
     }
 
     pub fn load_path(&mut self, p: &Path) -> Result<()> {
         self.items = p.parse()?;
+        self.page_counts.borrow_mut().clear();
         self.pos = Pos::Normal(0);
+        self.page = 0;
         Ok(())
     }
 }
 
 pub trait Handler: Debug {
-    fn handle(&self) -> Result<Rgba8Image>;
+    fn name(&self) -> String;
+
+    /// Number of sub-images, such as TIFF pages or icon sizes.
+    fn pages(&self) -> Result<usize> {
+        Ok(1)
+    }
+
+    fn handle(&self, page: usize, options: DecodeOptions) -> Result<Vec<Frame>>;
+
+    /// How the decoded image has to be transformed to be shown upright.
+    fn orientation(&self) -> Orientation {
+        Orientation::Normal
+    }
+
+    fn set_orientation(&self, _orientation: Orientation) -> Result<()> {
+        Err("orientation can't be saved".into())
+    }
+
+    fn metadata(&self) -> Result<Metadata>;
+
+    fn path(&self) -> Option<&Path> {
+        None
+    }
+}
+
+/// Decodes a page of `item` with `options`, turned upright if `auto_orient` is set.
+pub fn decode_item(
+    item: &dyn Handler,
+    page: usize,
+    options: DecodeOptions,
+    auto_orient: bool,
+) -> Result<Vec<Frame>> {
+    let mut frames = item.handle(page, options)?;
+    if auto_orient {
+        let orientation = item.orientation();
+        if orientation != Orientation::Normal {
+            for frame in &mut frames {
+                let buffer = std::mem::take(&mut frame.buffer);
+                frame.buffer = orientation.apply(buffer);
+            }
+        }
+    }
+    Ok(frames)
 }
 
 impl Handler for PathBuf {
-    fn handle(&self) -> Result<Rgba8Image> {
-        Ok(image::open(self)?.to_rgba8())
+    fn name(&self) -> String {
+        self.as_path().name()
+    }
+
+    fn pages(&self) -> Result<usize> {
+        self.as_path().pages()
+    }
+
+    fn handle(&self, page: usize, options: DecodeOptions) -> Result<Vec<Frame>> {
+        self.as_path().handle(page, options)
+    }
+
+    fn orientation(&self) -> Orientation {
+        self.as_path().orientation()
+    }
+
+    fn set_orientation(&self, orientation: Orientation) -> Result<()> {
+        self.as_path().set_orientation(orientation)
+    }
+
+    fn metadata(&self) -> Result<Metadata> {
+        Handler::metadata(self.as_path())
+    }
+
+    fn path(&self) -> Option<&Path> {
+        Some(self)
     }
 }
 
 impl Handler for Path {
-    fn handle(&self) -> Result<Rgba8Image> {
-        Ok(image::open(self)?.to_rgba8())
+    fn name(&self) -> String {
+        self.file_name()
+            .unwrap_or(self.as_os_str())
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    fn pages(&self) -> Result<usize> {
+        match Container::detect(self)? {
+            Some(container) => container.pages(self),
+            None => Ok(1),
+        }
+    }
+
+    fn handle(&self, page: usize, options: DecodeOptions) -> Result<Vec<Frame>> {
+        let icc = match options.color_manage {
+            // a profile that can't be read is treated like a missing one
+            true => color::icc_profile(self).ok().flatten(),
+            false => None,
+        };
+        let icc = match icc {
+            Some(icc) => icc,
+            None => return decode(self, page),
+        };
+
+        if page == 0 {
+            if let Some(img) = color::decode_cmyk_jpeg(self, &icc)? {
+                let mut frame = Frame::still(img.into());
+                frame.converted = true;
+                return Ok(vec![frame]);
+            }
+        }
+        let mut frames = decode(self, page)?;
+        for frame in &mut frames {
+            if let Err(e) = color::to_srgb(&icc, &mut frame.buffer) {
+                eprintln!("{}: {}", self.display(), e);
+                break;
+            }
+            frame.converted = true;
+        }
+        Ok(frames)
+    }
+
+    fn orientation(&self) -> Orientation {
+        Orientation::read(self)
+    }
+
+    fn set_orientation(&self, orientation: Orientation) -> Result<()> {
+        orientation.write(self)
     }
+
+    fn metadata(&self) -> Result<Metadata> {
+        Metadata::read(self)
+    }
+
+    fn path(&self) -> Option<&Path> {
+        Some(self)
+    }
+}
+
+fn decode(path: &Path, page: usize) -> Result<Vec<Frame>> {
+    match Container::detect(path)? {
+        Some(container) => {
+            return Ok(vec![Frame::still(container.decode(path, page)?.into())]);
+        }
+        None if page != 0 => return Err("no such page".into()),
+        None => {}
+    }
+
+    let reader = Reader::open(path)?.with_guessed_format()?;
+    let frames = match reader.format() {
+        Some(ImageFormat::Gif) => GifDecoder::new(reader.into_inner())?
+            .into_frames()
+            .collect_frames()?,
+        Some(ImageFormat::Png) => {
+            let decoder = PngDecoder::new(reader.into_inner())?;
+            if !decoder.is_apng() {
+                return Ok(vec![Frame::still(
+                    image::DynamicImage::from_decoder(decoder)?.into(),
+                )]);
+            }
+            decoder.apng().into_frames().collect_frames()?
+        }
+        Some(ImageFormat::WebP) => {
+            let decoder = WebPDecoder::new(reader.into_inner())?;
+            if !decoder.has_animation() {
+                return Ok(vec![Frame::still(
+                    image::DynamicImage::from_decoder(decoder)?.into(),
+                )]);
+            }
+            decoder.into_frames().collect_frames()?
+        }
+        _ => return Ok(vec![Frame::still(reader.decode()?.into())]),
+    };
+    Ok(frames.into_iter().map(Frame::from).collect())
 }
 
 pub trait Parser {
@@ -85,4 +419,4 @@ impl Parser for Path {
         }
         Ok(p)
     }
-}
\ No newline at end of file
+}
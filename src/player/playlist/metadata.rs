@@ -0,0 +1,429 @@
+use exif::{Exif, Field, In, Rational, Reader as ExifReader, Tag, Value};
+use image::{
+    codecs::{
+        bmp::BmpDecoder, gif::GifDecoder, ico::IcoDecoder, jpeg::JpegDecoder,
+        openexr::OpenExrDecoder, png::PngDecoder, tiff::TiffDecoder, webp::WebPDecoder,
+    },
+    io::Reader,
+    ExtendedColorType, ImageDecoder, ImageFormat,
+};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufReader, Seek},
+    path::{Path, PathBuf},
+};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Properties of an image file, as shown by the info overlay and `yume info`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Metadata {
+    pub path: PathBuf,
+    pub format: Option<String>,
+    pub file_size: u64,
+    pub width: u32,
+    pub height: u32,
+    pub color_type: String,
+    pub bit_depth: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exif: Option<ExifSummary>,
+    /// PNG tEXt, zTXt and iTXt chunks.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub text: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ExifSummary {
+    pub camera: Option<String>,
+    pub lens: Option<String>,
+    pub exposure_time: Option<String>,
+    pub f_number: Option<String>,
+    pub iso: Option<u32>,
+    pub focal_length: Option<String>,
+    pub date: Option<String>,
+    pub gps: Option<Gps>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Gps {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude: Option<f64>,
+}
+
+impl Metadata {
+    pub fn read(path: &Path) -> Result<Self> {
+        let file_size = std::fs::metadata(path)?.len();
+        let reader = Reader::open(path)?.with_guessed_format()?;
+        let format = reader.format();
+        let ((width, height), color_type) = probe(reader)?;
+        let text = match format {
+            Some(ImageFormat::Png) => png_text(&std::fs::read(path)?),
+            _ => BTreeMap::new(),
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            format: format.map(|f| format!("{:?}", f)),
+            file_size,
+            width,
+            height,
+            color_type: format!("{:?}", color_type),
+            bit_depth: bit_depth(color_type),
+            exif: read_exif(path)
+                .map(|exif| ExifSummary::new(&exif))
+                // TIFF files are EXIF containers, but rarely hold any of the fields we show
+                .filter(|exif| *exif != ExifSummary::default()),
+            text,
+        })
+    }
+
+    /// Human readable lines for the info overlay.
+    pub fn lines(&self) -> Vec<String> {
+        let name = self.path.file_name().unwrap_or(self.path.as_os_str());
+        let mut lines = vec![
+            name.to_string_lossy().into_owned(),
+            format!(
+                "{} {}x{} {} ({}-bit)",
+                self.format.as_deref().unwrap_or("unknown"),
+                self.width,
+                self.height,
+                self.color_type,
+                self.bit_depth
+            ),
+            format!("{} bytes", self.file_size),
+        ];
+        if let Some(exif) = &self.exif {
+            let fields = [
+                ("camera", exif.camera.clone()),
+                ("lens", exif.lens.clone()),
+                ("exposure", exif.exposure_time.clone()),
+                ("aperture", exif.f_number.clone()),
+                ("iso", exif.iso.map(|iso| iso.to_string())),
+                ("focal length", exif.focal_length.clone()),
+                ("date", exif.date.clone()),
+                (
+                    "gps",
+                    exif.gps
+                        .map(|gps| format!("{:.6}, {:.6}", gps.latitude, gps.longitude)),
+                ),
+            ];
+            for (key, value) in fields {
+                if let Some(value) = value {
+                    lines.push(format!("{}: {}", key, value));
+                }
+            }
+        }
+        for (key, value) in &self.text {
+            lines.push(format!("{}: {}", key, value));
+        }
+        lines
+    }
+}
+
+impl ExifSummary {
+    fn new(exif: &Exif) -> Self {
+        let field = |tag| exif.get_field(tag, In::PRIMARY);
+        let display =
+            |tag| field(tag).map(|f: &Field| f.display_value().with_unit(exif).to_string());
+        let ascii = |tag| {
+            field(tag).and_then(|f: &Field| match &f.value {
+                Value::Ascii(s) => s
+                    .first()
+                    .map(|s| String::from_utf8_lossy(s).trim().to_owned()),
+                _ => None,
+            })
+        };
+        let camera = match (ascii(Tag::Make), ascii(Tag::Model)) {
+            (Some(make), Some(model)) if model.starts_with(&make) => Some(model),
+            (Some(make), Some(model)) => Some(format!("{} {}", make, model)),
+            (make, model) => make.or(model),
+        };
+
+        Self {
+            camera,
+            lens: ascii(Tag::LensModel),
+            exposure_time: display(Tag::ExposureTime),
+            f_number: display(Tag::FNumber),
+            iso: field(Tag::PhotographicSensitivity).and_then(|f| f.value.get_uint(0)),
+            focal_length: display(Tag::FocalLength),
+            date: ascii(Tag::DateTimeOriginal).or_else(|| ascii(Tag::DateTime)),
+            gps: Gps::new(exif),
+        }
+    }
+}
+
+impl Gps {
+    fn new(exif: &Exif) -> Option<Self> {
+        let field = |tag| exif.get_field(tag, In::PRIMARY).map(|f| &f.value);
+        let degrees = |tag| match field(tag)? {
+            Value::Rational(dms) if dms.len() == 3 => {
+                Some(dms[0].to_f64() + dms[1].to_f64() / 60.0 + dms[2].to_f64() / 3600.0)
+            }
+            _ => None,
+        };
+        // southern latitudes and western longitudes are negative
+        let sign = |tag, negative: u8| match field(tag) {
+            Some(Value::Ascii(s)) if s.first().and_then(|s| s.first()) == Some(&negative) => -1.0,
+            _ => 1.0,
+        };
+        let altitude = match field(Tag::GPSAltitude) {
+            Some(Value::Rational(v)) => v.first().map(Rational::to_f64).map(|alt| {
+                // reference 1 means below sea level
+                match field(Tag::GPSAltitudeRef).and_then(|v| v.get_uint(0)) {
+                    Some(1) => -alt,
+                    _ => alt,
+                }
+            }),
+            _ => None,
+        };
+
+        Some(Self {
+            latitude: degrees(Tag::GPSLatitude)? * sign(Tag::GPSLatitudeRef, b'S'),
+            longitude: degrees(Tag::GPSLongitude)? * sign(Tag::GPSLongitudeRef, b'W'),
+            altitude,
+        })
+    }
+}
+
+fn read_exif(path: &Path) -> Option<Exif> {
+    let file = File::open(path).ok()?;
+    ExifReader::new()
+        .read_from_container(&mut BufReader::new(file))
+        .ok()
+}
+
+/// Reads dimensions and the stored color type without decoding pixels where `image` allows it.
+fn probe<R: std::io::BufRead + Seek>(reader: Reader<R>) -> Result<((u32, u32), ExtendedColorType)> {
+    fn info<'a>(decoder: impl ImageDecoder<'a>) -> ((u32, u32), ExtendedColorType) {
+        (decoder.dimensions(), decoder.original_color_type())
+    }
+
+    let format = reader.format().ok_or("unknown image format")?;
+    let decoded = |reader: Reader<R>| -> Result<_> {
+        let img = reader.decode()?;
+        Ok(((img.width(), img.height()), img.color().into()))
+    };
+    Ok(match format {
+        ImageFormat::Png => info(PngDecoder::new(reader.into_inner())?),
+        ImageFormat::Jpeg => info(JpegDecoder::new(reader.into_inner())?),
+        ImageFormat::Gif => info(GifDecoder::new(reader.into_inner())?),
+        ImageFormat::WebP => info(WebPDecoder::new(reader.into_inner())?),
+        ImageFormat::Tiff => info(TiffDecoder::new(reader.into_inner())?),
+        ImageFormat::Bmp => info(BmpDecoder::new(reader.into_inner())?),
+        ImageFormat::Ico => info(IcoDecoder::new(reader.into_inner())?),
+        ImageFormat::OpenExr => info(OpenExrDecoder::new(reader.into_inner())?),
+        _ => decoded(reader)?,
+    })
+}
+
+fn bit_depth(color_type: ExtendedColorType) -> u8 {
+    use ExtendedColorType::*;
+
+    match color_type {
+        L1 | La1 | Rgb1 | Rgba1 => 1,
+        L2 | La2 | Rgb2 | Rgba2 => 2,
+        L4 | La4 | Rgb4 | Rgba4 => 4,
+        L16 | La16 | Rgb16 | Rgba16 => 16,
+        Rgb32F | Rgba32F => 32,
+        Unknown(bits) => bits,
+        _ => 8,
+    }
+}
+
+/// Text chunks of a PNG file. Chunks after the image data count too, and ones that can't be
+/// decoded are left out.
+fn png_text(data: &[u8]) -> BTreeMap<String, String> {
+    let mut decoder = png::StreamingDecoder::new();
+    let mut image_data = Vec::new();
+    let mut rest = data;
+    while !rest.is_empty() {
+        image_data.clear();
+        match decoder.update(rest, &mut image_data) {
+            Ok((_, png::Decoded::ImageEnd)) => break,
+            Ok((consumed, _)) => rest = &rest[consumed..],
+            // a damaged chunk ends the text read so far, not the rest of the metadata
+            Err(_) => break,
+        }
+    }
+
+    let mut text = BTreeMap::new();
+    let info = match decoder.info() {
+        Some(info) => info,
+        None => return text,
+    };
+    for chunk in &info.uncompressed_latin1_text {
+        text.insert(chunk.keyword.clone(), chunk.text.clone());
+    }
+    for chunk in &info.compressed_latin1_text {
+        let mut chunk = chunk.clone();
+        if let Ok(value) = chunk.decompress_text().and_then(|_| chunk.get_text()) {
+            text.insert(chunk.keyword.clone(), value);
+        }
+    }
+    for chunk in &info.utf8_text {
+        let mut chunk = chunk.clone();
+        if let Ok(value) = chunk.decompress_text().and_then(|_| chunk.get_text()) {
+            text.insert(chunk.keyword.clone(), value);
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use exif::experimental::Writer;
+    use std::io::Cursor;
+
+    fn exif(fields: &[(Tag, Value)]) -> Exif {
+        let fields: Vec<_> = fields
+            .iter()
+            .map(|(tag, value)| Field {
+                tag: *tag,
+                ifd_num: In::PRIMARY,
+                value: value.clone(),
+            })
+            .collect();
+        let mut writer = Writer::new();
+        for field in &fields {
+            writer.push_field(field);
+        }
+        let mut tiff = Cursor::new(Vec::new());
+        writer.write(&mut tiff, false).unwrap();
+        ExifReader::new().read_raw(tiff.into_inner()).unwrap()
+    }
+
+    fn rationals(values: &[(u32, u32)]) -> Value {
+        Value::Rational(
+            values
+                .iter()
+                .map(|&(num, denom)| Rational { num, denom })
+                .collect(),
+        )
+    }
+
+    fn ascii(s: &str) -> Value {
+        Value::Ascii(vec![s.as_bytes().to_vec()])
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn gps_converts_degrees_minutes_seconds() {
+        let gps = Gps::new(&exif(&[
+            (Tag::GPSLatitudeRef, ascii("N")),
+            (Tag::GPSLatitude, rationals(&[(52, 1), (30, 1), (369, 10)])),
+            (Tag::GPSLongitudeRef, ascii("E")),
+            (Tag::GPSLongitude, rationals(&[(13, 1), (45, 2), (0, 1)])),
+            (Tag::GPSAltitudeRef, Value::Byte(vec![0])),
+            (Tag::GPSAltitude, rationals(&[(345, 10)])),
+        ]))
+        .unwrap();
+        assert_close(gps.latitude, 52.0 + 30.0 / 60.0 + 36.9 / 3600.0);
+        assert_close(gps.longitude, 13.0 + 22.5 / 60.0);
+        assert_close(gps.altitude.unwrap(), 34.5);
+    }
+
+    #[test]
+    fn gps_south_west_and_below_sea_level_are_negative() {
+        let gps = Gps::new(&exif(&[
+            (Tag::GPSLatitudeRef, ascii("S")),
+            (Tag::GPSLatitude, rationals(&[(33, 1), (52, 1), (0, 1)])),
+            (Tag::GPSLongitudeRef, ascii("W")),
+            (Tag::GPSLongitude, rationals(&[(70, 1), (30, 1), (0, 1)])),
+            (Tag::GPSAltitudeRef, Value::Byte(vec![1])),
+            (Tag::GPSAltitude, rationals(&[(430, 1)])),
+        ]))
+        .unwrap();
+        assert_close(gps.latitude, -(33.0 + 52.0 / 60.0));
+        assert_close(gps.longitude, -70.5);
+        assert_close(gps.altitude.unwrap(), -430.0);
+    }
+
+    #[test]
+    fn gps_needs_both_coordinates() {
+        let exif = exif(&[(Tag::GPSLatitude, rationals(&[(1, 1), (0, 1), (0, 1)]))]);
+        assert_eq!(Gps::new(&exif), None);
+    }
+
+    #[test]
+    fn camera_merges_make_and_model() {
+        let camera = |fields: &[(Tag, Value)]| ExifSummary::new(&exif(fields)).camera;
+        assert_eq!(
+            camera(&[
+                (Tag::Make, ascii("Canon")),
+                (Tag::Model, ascii("Canon EOS 5D"))
+            ]),
+            Some("Canon EOS 5D".to_owned())
+        );
+        assert_eq!(
+            camera(&[(Tag::Make, ascii("FUJIFILM")), (Tag::Model, ascii("X-T3"))]),
+            Some("FUJIFILM X-T3".to_owned())
+        );
+        assert_eq!(
+            camera(&[(Tag::Model, ascii("X-T3"))]),
+            Some("X-T3".to_owned())
+        );
+    }
+
+    #[test]
+    fn bit_depth_per_channel() {
+        assert_eq!(bit_depth(ExtendedColorType::Rgb8), 8);
+        assert_eq!(bit_depth(ExtendedColorType::La8), 8);
+        assert_eq!(bit_depth(ExtendedColorType::L16), 16);
+        assert_eq!(bit_depth(ExtendedColorType::Rgba16), 16);
+        assert_eq!(bit_depth(ExtendedColorType::Rgb32F), 32);
+        assert_eq!(bit_depth(ExtendedColorType::Rgba32F), 32);
+        assert_eq!(bit_depth(ExtendedColorType::L1), 1);
+    }
+
+    /// A 1×1 PNG with `before` text chunks ahead of the image data and raw `after` chunks
+    /// behind it.
+    fn png(before: &[(&str, &str)], after: &[([u8; 4], &[u8])]) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut encoder = png::Encoder::new(&mut data, 1, 1);
+        encoder.set_color(png::ColorType::Grayscale);
+        for (keyword, value) in before {
+            encoder
+                .add_text_chunk(keyword.to_string(), value.to_string())
+                .unwrap();
+        }
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&[0]).unwrap();
+        for &(name, chunk) in after {
+            writer
+                .write_chunk(png::chunk::ChunkType(name), chunk)
+                .unwrap();
+        }
+        writer.finish().unwrap();
+        data
+    }
+
+    #[test]
+    fn reads_text_after_the_image_data() {
+        let data = png(&[("Title", "before")], &[(*b"tEXt", b"Comment\0after")]);
+        let text = png_text(&data);
+        assert_eq!(text.get("Title").map(String::as_str), Some("before"));
+        assert_eq!(text.get("Comment").map(String::as_str), Some("after"));
+    }
+
+    #[test]
+    fn skips_text_that_fails_to_decompress() {
+        let data = png(
+            &[("Title", "kept")],
+            &[
+                (*b"zTXt", b"Broken\0\0not zlib"),
+                (*b"tEXt", b"Author\0also kept"),
+            ],
+        );
+        let text = png_text(&data);
+        assert_eq!(text.len(), 2);
+        assert!(!text.contains_key("Broken"));
+        assert_eq!(text.get("Author").map(String::as_str), Some("also kept"));
+    }
+}
@@ -0,0 +1,560 @@
+use crate::Pixels;
+use exif::{experimental::Writer, Field, In, Reader, Tag, Value};
+use image::{imageops, ImageBuffer, Pixel};
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io::{BufReader, Cursor, Write},
+    ops::Range,
+    path::Path,
+};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+
+/// Values of the EXIF Orientation tag, named after the transform that displays the image upright.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Orientation {
+    #[default]
+    Normal = 1,
+    FlipHorizontal = 2,
+    Rotate180 = 3,
+    FlipVertical = 4,
+    Transpose = 5,
+    Rotate90 = 6,
+    Transverse = 7,
+    Rotate270 = 8,
+}
+
+impl Orientation {
+    pub const fn from_exif(v: u32) -> Option<Self> {
+        Some(match v {
+            1 => Self::Normal,
+            2 => Self::FlipHorizontal,
+            3 => Self::Rotate180,
+            4 => Self::FlipVertical,
+            5 => Self::Transpose,
+            6 => Self::Rotate90,
+            7 => Self::Transverse,
+            8 => Self::Rotate270,
+            _ => return None,
+        })
+    }
+
+    /// Clockwise quarter turns, and whether the image is mirrored left to right before turning.
+    pub const fn parts(self) -> (u8, bool) {
+        match self {
+            Self::Normal => (0, false),
+            Self::FlipHorizontal => (0, true),
+            Self::Rotate90 => (1, false),
+            Self::Transverse => (1, true),
+            Self::Rotate180 => (2, false),
+            Self::FlipVertical => (2, true),
+            Self::Rotate270 => (3, false),
+            Self::Transpose => (3, true),
+        }
+    }
+
+    pub const fn from_parts(turns: u8, flip: bool) -> Self {
+        match (turns % 4, flip) {
+            (0, false) => Self::Normal,
+            (0, true) => Self::FlipHorizontal,
+            (1, false) => Self::Rotate90,
+            (1, true) => Self::Transverse,
+            (2, false) => Self::Rotate180,
+            (2, true) => Self::FlipVertical,
+            (3, false) => Self::Rotate270,
+            _ => Self::Transpose,
+        }
+    }
+
+    /// The orientation that applies `self` first, then `next`.
+    pub const fn then(self, next: Self) -> Self {
+        let (turns, flip) = self.parts();
+        let (next_turns, next_flip) = next.parts();
+        // a mirror reverses the direction of the turns made before it
+        let turns = if next_flip { 4 - turns } else { turns };
+        Self::from_parts(turns + next_turns, flip != next_flip)
+    }
+
+    /// Reads the orientation of `path`. Files without EXIF data are upright.
+    pub fn read(path: &Path) -> Self {
+        let exif = match File::open(path)
+            .map_err(exif::Error::from)
+            .and_then(|f| Reader::new().read_from_container(&mut BufReader::new(f)))
+        {
+            Ok(exif) => exif,
+            Err(_) => return Self::Normal,
+        };
+        exif.get_field(Tag::Orientation, In::PRIMARY)
+            .and_then(|field| field.value.get_uint(0))
+            .and_then(Self::from_exif)
+            .unwrap_or_default()
+    }
+
+    pub fn apply(self, img: Pixels) -> Pixels {
+        match img {
+            Pixels::Rgba8(img) => Pixels::Rgba8(self.transform(img)),
+            Pixels::Rgba16(img) => Pixels::Rgba16(self.transform(img)),
+            Pixels::Rgba32F(img) => Pixels::Rgba32F(self.transform(img)),
+        }
+    }
+
+    fn transform<P: Pixel + 'static>(
+        self,
+        img: ImageBuffer<P, Vec<P::Subpixel>>,
+    ) -> ImageBuffer<P, Vec<P::Subpixel>> {
+        match self {
+            Self::Normal => img,
+            Self::FlipHorizontal => imageops::flip_horizontal(&img),
+            Self::Rotate180 => imageops::rotate180(&img),
+            Self::FlipVertical => imageops::flip_vertical(&img),
+            Self::Transpose => imageops::flip_horizontal(&imageops::rotate90(&img)),
+            Self::Rotate90 => imageops::rotate90(&img),
+            Self::Transverse => imageops::flip_vertical(&imageops::rotate90(&img)),
+            Self::Rotate270 => imageops::rotate270(&img),
+        }
+    }
+}
+
+impl Orientation {
+    /// Stores the orientation in the EXIF data of a JPEG file without touching the image data.
+    pub fn write(self, path: &Path) -> Result<()> {
+        let data = self.patch(fs::read(path)?)?;
+        replace(path, &data)
+    }
+
+    /// `data`, a JPEG file, with its orientation set to `self`.
+    fn patch(self, mut data: Vec<u8>) -> Result<Vec<u8>> {
+        if !data.starts_with(&[0xff, 0xd8]) {
+            return Err("orientation can only be saved to JPEG files".into());
+        }
+        let value = self as u16;
+
+        match exif_segment(&data)? {
+            Some(segment) => {
+                let tiff = segment.start + EXIF_HEADER.len()..segment.end;
+                if !patch_orientation(&mut data[tiff.clone()], value)? {
+                    let mut exif = data[tiff].to_vec();
+                    insert_orientation(&mut exif, value)?;
+                    // the new payload comes with its own length
+                    data.splice(segment.start - 2..segment.end, app1_payload(&exif)?);
+                }
+            }
+            None => {
+                let mut writer = Writer::new();
+                let orientation = Field {
+                    tag: Tag::Orientation,
+                    ifd_num: In::PRIMARY,
+                    value: Value::Short(vec![value]),
+                };
+                writer.push_field(&orientation);
+                let mut tiff = Cursor::new(Vec::new());
+                writer.write(&mut tiff, false)?;
+
+                let mut segment = vec![0xff, 0xe1];
+                segment.extend(app1_payload(tiff.get_ref())?);
+                // JFIF wants its APP0 segment right after the start of image
+                let at = match data.get(2..6) {
+                    Some([0xff, 0xe0, hi, lo]) => 4 + u16::from_be_bytes([*hi, *lo]) as usize,
+                    _ => 2,
+                };
+                data.splice(at..at, segment);
+            }
+        }
+        Ok(data)
+    }
+}
+
+/// Replaces the contents of `path` without ever leaving a partly written file behind.
+fn replace(path: &Path, data: &[u8]) -> Result<()> {
+    let mut name = OsString::from(".");
+    name.push(path.file_name().ok_or("not a file")?);
+    name.push(format!(".yume-{}", std::process::id()));
+    let temp = path.with_file_name(name);
+    let permissions = fs::metadata(path)?.permissions();
+
+    let written = File::create(&temp).and_then(|mut file| {
+        file.write_all(data)?;
+        file.set_permissions(permissions)?;
+        file.sync_all()
+    });
+    if let Err(e) = written.and_then(|_| fs::rename(&temp, path)) {
+        let _ = fs::remove_file(&temp);
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// Range of the APP1 segment holding EXIF data, starting after its length.
+fn exif_segment(data: &[u8]) -> Result<Option<Range<usize>>> {
+    let mut pos = 2;
+    loop {
+        let header = data.get(pos..pos + 4).ok_or("truncated JPEG")?;
+        if header[0] != 0xff {
+            return Err("invalid JPEG segment".into());
+        }
+        // metadata always precedes the start of scan
+        if header[1] == 0xda {
+            return Ok(None);
+        }
+        let len = u16::from_be_bytes([header[2], header[3]]) as usize;
+        if len < 2 {
+            return Err("invalid JPEG segment".into());
+        }
+        let payload = pos + 4..pos + 2 + len;
+        let is_exif = data
+            .get(payload.clone())
+            .is_some_and(|p| p.starts_with(EXIF_HEADER));
+        if header[1] == 0xe1 && is_exif {
+            return Ok(Some(payload));
+        }
+        pos = payload.end;
+    }
+}
+
+/// Length field, EXIF header and `tiff` data of an APP1 segment.
+fn app1_payload(tiff: &[u8]) -> Result<Vec<u8>> {
+    let len = u16::try_from(2 + EXIF_HEADER.len() + tiff.len())
+        .map_err(|_| "EXIF data too large for a JPEG segment")?;
+    let mut payload = len.to_be_bytes().to_vec();
+    payload.extend_from_slice(EXIF_HEADER);
+    payload.extend_from_slice(tiff);
+    Ok(payload)
+}
+
+/// Byte order of TIFF data, from its header.
+fn big_endian(tiff: &[u8]) -> Result<bool> {
+    match tiff.get(..2) {
+        Some(b"MM") => Ok(true),
+        Some(b"II") => Ok(false),
+        _ => Err("invalid EXIF byte order".into()),
+    }
+}
+
+fn u16_at(tiff: &[u8], at: usize, big_endian: bool) -> Result<u16> {
+    let b = tiff.get(at..at + 2).ok_or("truncated EXIF data")?;
+    let b = [b[0], b[1]];
+    Ok(if big_endian {
+        u16::from_be_bytes(b)
+    } else {
+        u16::from_le_bytes(b)
+    })
+}
+
+fn u32_at(tiff: &[u8], at: usize, big_endian: bool) -> Result<u32> {
+    let b = tiff.get(at..at + 4).ok_or("truncated EXIF data")?;
+    let b = [b[0], b[1], b[2], b[3]];
+    Ok(if big_endian {
+        u32::from_be_bytes(b)
+    } else {
+        u32::from_le_bytes(b)
+    })
+}
+
+/// Offset and entries of the first IFD.
+fn first_ifd(tiff: &[u8], big_endian: bool) -> Result<(usize, Range<usize>)> {
+    let ifd = u32_at(tiff, 4, big_endian)? as usize;
+    let count = u16_at(tiff, ifd, big_endian)? as usize;
+    let entries = ifd + 2..ifd + 2 + count * 12;
+    if tiff.len() < entries.end + 4 {
+        return Err("truncated EXIF data".into());
+    }
+    Ok((ifd, entries))
+}
+
+/// Overwrites an existing Orientation entry in the first IFD, returning whether there was one.
+fn patch_orientation(tiff: &mut [u8], value: u16) -> Result<bool> {
+    let big_endian = big_endian(tiff)?;
+    let (_, entries) = first_ifd(tiff, big_endian)?;
+    for entry in entries.step_by(12) {
+        // a single SHORT, stored in the entry itself
+        if u16_at(tiff, entry, big_endian)? == Tag::Orientation.number()
+            && u16_at(tiff, entry + 2, big_endian)? == 3
+        {
+            let bytes = if big_endian {
+                value.to_be_bytes()
+            } else {
+                value.to_le_bytes()
+            };
+            tiff[entry + 8..entry + 10].copy_from_slice(&bytes);
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Adds an Orientation entry to the first IFD.
+///
+/// The IFD is copied to the end with the new entry and everything else stays where it is, so
+/// offsets into the data, including private ones such as those of maker notes, remain valid.
+fn insert_orientation(tiff: &mut Vec<u8>, value: u16) -> Result<()> {
+    let big_endian = big_endian(tiff)?;
+    let (_, entries) = first_ifd(tiff, big_endian)?;
+    let u16_bytes = |v: u16| {
+        if big_endian {
+            v.to_be_bytes()
+        } else {
+            v.to_le_bytes()
+        }
+    };
+    let u32_bytes = |v: u32| {
+        if big_endian {
+            v.to_be_bytes()
+        } else {
+            v.to_le_bytes()
+        }
+    };
+
+    let tag = Tag::Orientation.number();
+    let mut orientation = Vec::with_capacity(12);
+    orientation.extend(u16_bytes(tag));
+    orientation.extend(u16_bytes(3));
+    orientation.extend(u32_bytes(1));
+    orientation.extend(u16_bytes(value));
+    orientation.extend([0, 0]);
+
+    // entries are sorted by tag
+    let mut ifd = Vec::with_capacity(entries.len() + 18);
+    ifd.extend(u16_bytes((entries.len() / 12 + 1) as u16));
+    let mut inserted = false;
+    for entry in entries.clone().step_by(12) {
+        if !inserted && u16_at(tiff, entry, big_endian)? > tag {
+            ifd.extend_from_slice(&orientation);
+            inserted = true;
+        }
+        ifd.extend_from_slice(&tiff[entry..entry + 12]);
+    }
+    if !inserted {
+        ifd.extend_from_slice(&orientation);
+    }
+    // offset of the next IFD
+    ifd.extend_from_slice(&tiff[entries.end..entries.end + 4]);
+
+    // IFDs start on a word boundary
+    if tiff.len() % 2 == 1 {
+        tiff.push(0);
+    }
+    let at = u32::try_from(tiff.len()).map_err(|_| "EXIF data too large")?;
+    tiff.extend(ifd);
+    tiff[4..8].copy_from_slice(&u32_bytes(at));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all() -> impl Iterator<Item = Orientation> {
+        (1..=8).map(|v| Orientation::from_exif(v).unwrap())
+    }
+
+    /// A 3 by 2 image whose pixels are all different.
+    fn sample() -> Pixels {
+        Pixels::Rgba8(ImageBuffer::from_fn(3, 2, |x, y| {
+            image::Rgba([x as u8, y as u8, 0, 255])
+        }))
+    }
+
+    fn raw(img: Pixels) -> Vec<u8> {
+        match img {
+            Pixels::Rgba8(img) => img.into_raw(),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn applies_each_orientation() {
+        // output rows, each pixel named by where it was in `sample`
+        type Rows = &'static [&'static [(u8, u8)]];
+        let cases: [(Orientation, Rows); 8] = [
+            (
+                Orientation::Normal,
+                &[&[(0, 0), (1, 0), (2, 0)], &[(0, 1), (1, 1), (2, 1)]],
+            ),
+            (
+                Orientation::FlipHorizontal,
+                &[&[(2, 0), (1, 0), (0, 0)], &[(2, 1), (1, 1), (0, 1)]],
+            ),
+            (
+                Orientation::Rotate180,
+                &[&[(2, 1), (1, 1), (0, 1)], &[(2, 0), (1, 0), (0, 0)]],
+            ),
+            (
+                Orientation::FlipVertical,
+                &[&[(0, 1), (1, 1), (2, 1)], &[(0, 0), (1, 0), (2, 0)]],
+            ),
+            (
+                Orientation::Transpose,
+                &[&[(0, 0), (0, 1)], &[(1, 0), (1, 1)], &[(2, 0), (2, 1)]],
+            ),
+            (
+                Orientation::Rotate90,
+                &[&[(0, 1), (0, 0)], &[(1, 1), (1, 0)], &[(2, 1), (2, 0)]],
+            ),
+            (
+                Orientation::Transverse,
+                &[&[(2, 1), (2, 0)], &[(1, 1), (1, 0)], &[(0, 1), (0, 0)]],
+            ),
+            (
+                Orientation::Rotate270,
+                &[&[(2, 0), (2, 1)], &[(1, 0), (1, 1)], &[(0, 0), (0, 1)]],
+            ),
+        ];
+        for (o, rows) in cases {
+            let img = o.apply(sample());
+            assert_eq!(
+                img.dimensions(),
+                (rows[0].len() as u32, rows.len() as u32),
+                "{:?}",
+                o
+            );
+            let expected: Vec<u8> = rows
+                .iter()
+                .flat_map(|row| row.iter().flat_map(|&(x, y)| [x, y, 0, 255]))
+                .collect();
+            assert_eq!(raw(img), expected, "{:?}", o);
+        }
+    }
+
+    #[test]
+    fn parts_round_trip() {
+        for o in all() {
+            let (turns, flip) = o.parts();
+            assert_eq!(Orientation::from_parts(turns, flip), o);
+        }
+    }
+
+    #[test]
+    fn composition_matches_the_transforms() {
+        for a in all() {
+            for b in all() {
+                assert_eq!(
+                    raw(b.apply(a.apply(sample()))),
+                    raw(a.then(b).apply(sample())),
+                    "{:?} then {:?}",
+                    a,
+                    b
+                );
+            }
+        }
+    }
+
+    /// A JPEG without image data, with a JFIF segment and an APP1 segment of `exif` if any.
+    fn jpeg(exif: Option<&[&Field]>) -> Vec<u8> {
+        let mut data = vec![0xff, 0xd8, 0xff, 0xe0, 0, 16];
+        data.extend(b"JFIF\0\x01\x01\0\0\x01\0\x01\0\0");
+        if let Some(fields) = exif {
+            let mut writer = Writer::new();
+            for f in fields {
+                writer.push_field(f);
+            }
+            let mut tiff = Cursor::new(Vec::new());
+            writer.write(&mut tiff, false).unwrap();
+            data.extend([0xff, 0xe1]);
+            data.extend(app1_payload(tiff.get_ref()).unwrap());
+        }
+        data.extend([0xff, 0xda, 0, 2, 0xff, 0xd9]);
+        data
+    }
+
+    fn read(data: &[u8]) -> exif::Exif {
+        Reader::new()
+            .read_from_container(&mut Cursor::new(data))
+            .unwrap()
+    }
+
+    fn orientation(exif: &exif::Exif) -> Option<u32> {
+        exif.get_field(Tag::Orientation, In::PRIMARY)?
+            .value
+            .get_uint(0)
+    }
+
+    fn field(tag: Tag, value: Value) -> Field {
+        Field {
+            tag,
+            ifd_num: In::PRIMARY,
+            value,
+        }
+    }
+
+    #[test]
+    fn adds_exif_to_plain_jpegs() {
+        let data = Orientation::Rotate90.patch(jpeg(None)).unwrap();
+        assert_eq!(orientation(&read(&data)), Some(6));
+        // after the JFIF segment, and the image data is untouched
+        assert_eq!(&data[..6], &jpeg(None)[..6]);
+        assert!(data.ends_with(&[0xff, 0xda, 0, 2, 0xff, 0xd9]));
+    }
+
+    #[test]
+    fn adds_orientation_keeping_other_fields() {
+        let make = field(Tag::Make, Value::Ascii(vec![b"yume".to_vec()]));
+        let width = field(Tag::ImageWidth, Value::Long(vec![640]));
+        let date = field(
+            Tag::DateTimeOriginal,
+            Value::Ascii(vec![b"2020:01:02 03:04:05".to_vec()]),
+        );
+        let original = jpeg(Some(&[&make, &width, &date]));
+        assert_eq!(orientation(&read(&original)), None);
+
+        let data = Orientation::Transverse.patch(original.clone()).unwrap();
+        let exif = read(&data);
+        assert_eq!(orientation(&exif), Some(7));
+        for f in [&make, &width, &date] {
+            let found = exif.get_field(f.tag, In::PRIMARY).unwrap();
+            assert_eq!(
+                found.display_value().to_string(),
+                f.display_value().to_string()
+            );
+        }
+        // the old data stays where it was
+        let segment = exif_segment(&original).unwrap().unwrap();
+        assert_eq!(
+            &data[segment.start + 14..segment.end],
+            &original[segment.start + 14..segment.end]
+        );
+    }
+
+    #[test]
+    fn patches_existing_orientation_in_place() {
+        let make = field(Tag::Make, Value::Ascii(vec![b"yume".to_vec()]));
+        let upside_down = field(Tag::Orientation, Value::Short(vec![3]));
+        let original = jpeg(Some(&[&make, &upside_down]));
+
+        let data = Orientation::Rotate270.patch(original.clone()).unwrap();
+        assert_eq!(data.len(), original.len());
+        assert_eq!(orientation(&read(&data)), Some(8));
+        let data = Orientation::Normal.patch(data).unwrap();
+        assert_eq!(orientation(&read(&data)), Some(1));
+    }
+
+    #[test]
+    fn rejects_broken_files() {
+        assert!(Orientation::Rotate90.patch(b"GIF89a".to_vec()).is_err());
+        assert!(Orientation::Rotate90.patch(vec![0xff, 0xd8, 0xff]).is_err());
+        assert!(Orientation::Rotate90
+            .patch(vec![0xff, 0xd8, 0xff, 0xe1, 0, 0])
+            .is_err());
+        // an IFD past the end of the EXIF data
+        let mut truncated = vec![0xff, 0xd8, 0xff, 0xe1];
+        truncated.extend(app1_payload(b"II*\0\0\x01\0\0").unwrap());
+        truncated.extend([0xff, 0xda, 0, 2, 0xff, 0xd9]);
+        assert!(Orientation::Rotate90.patch(truncated).is_err());
+    }
+
+    #[test]
+    fn writes_files_atomically() {
+        let dir = std::env::temp_dir().join(format!("yume-orientation-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("photo.jpg");
+        fs::write(&path, jpeg(None)).unwrap();
+
+        Orientation::Rotate180.write(&path).unwrap();
+        assert_eq!(Orientation::read(&path), Orientation::Rotate180);
+        // only the photo is left
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,272 @@
+use image::{DynamicImage, ImageBuffer, ImageFormat, Luma, Rgb};
+use std::{
+    cmp::Reverse,
+    fs::File,
+    io::{BufReader, Read, Seek},
+    path::Path,
+};
+use tiff::{
+    decoder::{Decoder, DecodingResult},
+    ColorType,
+};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+const ICON_HEADER_LEN: usize = 6;
+const ICON_ENTRY_LEN: usize = 16;
+
+/// Containers that may hold more than one image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Container {
+    /// Multi-page TIFF, pages are kept in file order.
+    Tiff,
+    /// ICO or CUR, sizes are ordered from the largest to the smallest.
+    Icon,
+}
+
+struct IconEntry {
+    offset: usize,
+    len: usize,
+    area: u32,
+    bits_per_pixel: u16,
+    raw: [u8; ICON_ENTRY_LEN],
+}
+
+impl Container {
+    pub fn detect(path: &Path) -> std::io::Result<Option<Self>> {
+        let mut magic = [0u8; 4];
+        let mut file = File::open(path)?;
+        if file.read(&mut magic)? < magic.len() {
+            return Ok(None);
+        }
+        Ok(match magic {
+            [b'I', b'I', 42 | 43, 0] | [b'M', b'M', 0, 42 | 43] => Some(Self::Tiff),
+            [0, 0, 1 | 2, 0] => Some(Self::Icon),
+            _ => None,
+        })
+    }
+
+    pub fn pages(&self, path: &Path) -> Result<usize> {
+        match self {
+            Self::Tiff => tiff_pages(BufReader::new(File::open(path)?)),
+            Self::Icon => Ok(icon_entries(&std::fs::read(path)?)?.len()),
+        }
+    }
+
+    pub fn decode(&self, path: &Path, page: usize) -> Result<DynamicImage> {
+        match self {
+            Self::Tiff => tiff_page(BufReader::new(File::open(path)?), page),
+            Self::Icon => icon_page(&std::fs::read(path)?, page),
+        }
+    }
+}
+
+/// Counts the pages by following the chain of image directories, which fails on a broken or
+/// looping chain.
+fn tiff_pages(reader: impl Read + Seek) -> Result<usize> {
+    let mut decoder = Decoder::new(reader)?;
+    let mut pages = 1;
+    while decoder.more_images() {
+        decoder.next_image()?;
+        pages += 1;
+    }
+    Ok(pages)
+}
+
+/// Decodes any page, the first one included, so all pages support the same color types.
+fn tiff_page(reader: impl Read + Seek, page: usize) -> Result<DynamicImage> {
+    let mut decoder = Decoder::new(reader)?;
+    decoder.seek_to_image(page)?;
+    let (width, height) = decoder.dimensions()?;
+    let color_type = decoder.colortype()?;
+    let img = match (color_type, decoder.read_image()?) {
+        (ColorType::Gray(1), DecodingResult::U8(buf)) => {
+            let stride = (width as usize).div_ceil(8);
+            let bit = |x: u32, y: u32| buf[y as usize * stride + x as usize / 8] << (x % 8) & 0x80;
+            Some(DynamicImage::ImageLuma8(ImageBuffer::from_fn(
+                width,
+                height,
+                |x, y| Luma([if bit(x, y) != 0 { 255 } else { 0 }]),
+            )))
+        }
+        (ColorType::Gray(8), DecodingResult::U8(buf)) => {
+            ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageLuma8)
+        }
+        (ColorType::Gray(16), DecodingResult::U16(buf)) => {
+            ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageLuma16)
+        }
+        (ColorType::GrayA(8), DecodingResult::U8(buf)) => {
+            ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageLumaA8)
+        }
+        (ColorType::GrayA(16), DecodingResult::U16(buf)) => {
+            ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageLumaA16)
+        }
+        (ColorType::RGB(8), DecodingResult::U8(buf)) => {
+            ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageRgb8)
+        }
+        (ColorType::RGB(16), DecodingResult::U16(buf)) => {
+            ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageRgb16)
+        }
+        (ColorType::RGB(32), DecodingResult::F32(buf)) => {
+            ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageRgb32F)
+        }
+        (ColorType::RGBA(8), DecodingResult::U8(buf)) => {
+            ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageRgba8)
+        }
+        (ColorType::RGBA(16), DecodingResult::U16(buf)) => {
+            ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageRgba16)
+        }
+        (ColorType::RGBA(32), DecodingResult::F32(buf)) => {
+            ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageRgba32F)
+        }
+        (ColorType::CMYK(8), DecodingResult::U8(buf)) => {
+            let rgb = buf
+                .chunks_exact(4)
+                .flat_map(|cmyk| {
+                    let k = 255 - cmyk[3] as u16;
+                    let [c, m, y] =
+                        [cmyk[0], cmyk[1], cmyk[2]].map(|v| ((255 - v as u16) * k / 255) as u8);
+                    [c, m, y]
+                })
+                .collect();
+            ImageBuffer::<Rgb<u8>, _>::from_raw(width, height, rgb).map(DynamicImage::ImageRgb8)
+        }
+        _ => None,
+    };
+    img.ok_or_else(|| format!("unsupported TIFF color type {:?}", color_type).into())
+}
+
+fn icon_entries(data: &[u8]) -> Result<Vec<IconEntry>> {
+    let count = data
+        .get(4..ICON_HEADER_LEN)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]) as usize)
+        .ok_or("truncated icon header")?;
+    let mut entries = Vec::with_capacity(count);
+    for i in 0..count {
+        let start = ICON_HEADER_LEN + i * ICON_ENTRY_LEN;
+        let raw: [u8; ICON_ENTRY_LEN] = data
+            .get(start..start + ICON_ENTRY_LEN)
+            .ok_or("truncated icon directory")?
+            .try_into()?;
+        // a stored size of 0 means 256 pixels
+        let size = |v: u8| if v == 0 { 256 } else { v as u32 };
+        let le32 = |at: usize| u32::from_le_bytes([raw[at], raw[at + 1], raw[at + 2], raw[at + 3]]);
+        entries.push(IconEntry {
+            len: le32(8) as usize,
+            offset: le32(12) as usize,
+            area: size(raw[0]) * size(raw[1]),
+            bits_per_pixel: u16::from_le_bytes([raw[6], raw[7]]),
+            raw,
+        });
+    }
+    entries.sort_by_key(|e| Reverse((e.area, e.bits_per_pixel)));
+    Ok(entries)
+}
+
+/// Decodes one size of an icon by handing a single-entry copy of it to `image`,
+/// which otherwise only ever picks the largest one.
+fn icon_page(data: &[u8], page: usize) -> Result<DynamicImage> {
+    let entries = icon_entries(data)?;
+    let entry = entries.get(page).ok_or("no such icon page")?;
+    let payload = data
+        .get(entry.offset..entry.offset + entry.len)
+        .ok_or("truncated icon image")?;
+
+    let offset = (ICON_HEADER_LEN + ICON_ENTRY_LEN) as u32;
+    let mut single = Vec::with_capacity(offset as usize + payload.len());
+    single.extend_from_slice(&[0, 0, 1, 0, 1, 0]);
+    single.extend_from_slice(&entry.raw[..12]);
+    single.extend_from_slice(&offset.to_le_bytes());
+    single.extend_from_slice(payload);
+    Ok(image::load_from_memory_with_format(
+        &single,
+        ImageFormat::Ico,
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use tiff::encoder::{colortype::Gray8, TiffEncoder};
+
+    /// A TIFF with one 2×1 gray page per value, both pixels set to it.
+    fn tiff(values: &[u8]) -> Vec<u8> {
+        let mut data = Cursor::new(Vec::new());
+        let mut encoder = TiffEncoder::new(&mut data).unwrap();
+        for &v in values {
+            encoder.write_image::<Gray8>(2, 1, &[v, v]).unwrap();
+        }
+        data.into_inner()
+    }
+
+    /// An icon directory with entries of `sizes` pixels, each pointing at `len` bytes at
+    /// `offset`.
+    fn icon(sizes: &[u8], offset: u32, len: u32) -> Vec<u8> {
+        let mut data = vec![0, 0, 1, 0, sizes.len() as u8, 0];
+        for &size in sizes {
+            data.extend_from_slice(&[size, size, 0, 0, 1, 0, 32, 0]);
+            data.extend_from_slice(&len.to_le_bytes());
+            data.extend_from_slice(&offset.to_le_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn decodes_every_tiff_page_alike() {
+        let data = tiff(&[10, 20, 30]);
+        assert_eq!(tiff_pages(Cursor::new(&data)).unwrap(), 3);
+        for (page, v) in [10, 20, 30].into_iter().enumerate() {
+            let img = tiff_page(Cursor::new(&data), page).unwrap();
+            assert_eq!(img.to_luma8().into_raw(), vec![v, v]);
+        }
+        assert!(tiff_page(Cursor::new(&data), 3).is_err());
+    }
+
+    /// Where the offset of the directory after the little-endian one at `ifd` is stored.
+    fn next_pointer(data: &[u8], ifd: usize) -> usize {
+        ifd + 2 + 12 * u16::from_le_bytes([data[ifd], data[ifd + 1]]) as usize
+    }
+
+    fn u32_at(data: &[u8], at: usize) -> usize {
+        u32::from_le_bytes(data[at..at + 4].try_into().unwrap()) as usize
+    }
+
+    #[test]
+    fn rejects_broken_tiff_chains() {
+        let data = tiff(&[10, 20]);
+        let first = u32_at(&data, 4);
+        let second = u32_at(&data, next_pointer(&data, first));
+        assert!(tiff_pages(Cursor::new(&data[..second + 6])).is_err());
+        assert!(tiff_pages(Cursor::new(&data[..3])).is_err());
+
+        // the last directory pointing back at the first one
+        let mut looping = data.clone();
+        let at = next_pointer(&looping, second);
+        looping[at..at + 4].copy_from_slice(&(first as u32).to_le_bytes());
+        assert!(tiff_pages(Cursor::new(&looping)).is_err());
+    }
+
+    #[test]
+    fn sorts_icon_entries_largest_first() {
+        let data = icon(&[16, 0, 32], 0, 0);
+        let areas: Vec<_> = icon_entries(&data)
+            .unwrap()
+            .iter()
+            .map(|e| e.area)
+            .collect();
+        assert_eq!(areas, vec![256 * 256, 32 * 32, 16 * 16]);
+    }
+
+    #[test]
+    fn rejects_truncated_icons() {
+        assert!(icon_entries(&[0, 0, 1, 0]).is_err());
+        let data = icon(&[16, 32], 0, 0);
+        assert!(icon_entries(&data[..data.len() - 1]).is_err());
+
+        // an entry pointing past the end of the file
+        let data = icon(&[16], 1000, 64);
+        assert!(icon_page(&data, 0).is_err());
+        assert!(icon_page(&data, 1).is_err());
+    }
+}
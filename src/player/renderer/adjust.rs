@@ -0,0 +1,196 @@
+use bytemuck::{Pod, Zeroable};
+
+/// Display-only color changes, applied to sRGB-encoded values after tone mapping.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Adjustments {
+    /// Added to every channel.
+    pub brightness: f32,
+    /// Stretches the distance from mid gray.
+    pub contrast: f32,
+    /// Values above 1 brighten the shadows.
+    pub gamma: f32,
+    /// 0 is grayscale.
+    pub saturation: f32,
+    pub invert: bool,
+}
+
+/// One of the values in `Adjustments` changed by a slider key.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Adjustment {
+    #[default]
+    Brightness,
+    Contrast,
+    Gamma,
+    Saturation,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub(super) struct AdjustUniform {
+    brightness: f32,
+    contrast: f32,
+    gamma: f32,
+    saturation: f32,
+    invert: u32,
+    _padding: [u32; 3],
+}
+
+impl Default for Adjustments {
+    fn default() -> Self {
+        Self {
+            brightness: 0.0,
+            contrast: 1.0,
+            gamma: 1.0,
+            saturation: 1.0,
+            invert: false,
+        }
+    }
+}
+
+impl Adjustments {
+    pub fn get(&self, which: Adjustment) -> f32 {
+        match which {
+            Adjustment::Brightness => self.brightness,
+            Adjustment::Contrast => self.contrast,
+            Adjustment::Gamma => self.gamma,
+            Adjustment::Saturation => self.saturation,
+        }
+    }
+
+    /// Moves `which` by `steps` steps, negative ones going down.
+    pub fn step(&mut self, which: Adjustment, steps: f32) {
+        match which {
+            Adjustment::Brightness => {
+                self.brightness = (self.brightness + steps * 0.05).clamp(-1.0, 1.0)
+            }
+            Adjustment::Contrast => self.contrast = (self.contrast + steps * 0.1).clamp(0.0, 4.0),
+            // multiplicative so 0.5 is as far from 1 as 2
+            Adjustment::Gamma => self.gamma = (self.gamma * 1.1f32.powf(steps)).clamp(0.1, 10.0),
+            Adjustment::Saturation => {
+                self.saturation = (self.saturation + steps * 0.1).clamp(0.0, 4.0)
+            }
+        }
+    }
+
+    /// Lines of the overlay, marking the adjustment the keys change.
+    pub fn lines(&self, selected: Adjustment) -> Vec<String> {
+        let mut lines: Vec<_> = Adjustment::ALL
+            .iter()
+            .map(|&which| {
+                let marker = if which == selected { '>' } else { ' ' };
+                format!("{} {:<10} {:>5.2}", marker, which.name(), self.get(which))
+            })
+            .collect();
+        lines.push(format!(
+            "  {:<10} {:>5}",
+            "invert",
+            if self.invert { "on" } else { "off" }
+        ));
+        lines
+    }
+
+    pub(super) fn uniform(&self) -> AdjustUniform {
+        AdjustUniform {
+            brightness: self.brightness,
+            contrast: self.contrast,
+            gamma: self.gamma,
+            saturation: self.saturation,
+            invert: self.invert as u32,
+            _padding: [0; 3],
+        }
+    }
+}
+
+impl Adjustment {
+    pub const ALL: [Self; 4] = [
+        Self::Brightness,
+        Self::Contrast,
+        Self::Gamma,
+        Self::Saturation,
+    ];
+
+    pub fn next(self) -> Self {
+        match self {
+            Self::Brightness => Self::Contrast,
+            Self::Contrast => Self::Gamma,
+            Self::Gamma => Self::Saturation,
+            Self::Saturation => Self::Brightness,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Brightness => "brightness",
+            Self::Contrast => "contrast",
+            Self::Gamma => "gamma",
+            Self::Saturation => "saturation",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-5, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn steps_stay_in_range() {
+        let mut adjustments = Adjustments::default();
+        adjustments.step(Adjustment::Brightness, 2.0);
+        assert_close(adjustments.brightness, 0.1);
+        adjustments.step(Adjustment::Brightness, -100.0);
+        assert_eq!(adjustments.brightness, -1.0);
+        adjustments.step(Adjustment::Contrast, 100.0);
+        assert_eq!(adjustments.contrast, 4.0);
+        adjustments.step(Adjustment::Saturation, -100.0);
+        assert_eq!(adjustments.saturation, 0.0);
+        adjustments.step(Adjustment::Gamma, -1000.0);
+        assert_eq!(adjustments.gamma, 0.1);
+    }
+
+    #[test]
+    fn gamma_steps_undo_each_other() {
+        let mut adjustments = Adjustments::default();
+        adjustments.step(Adjustment::Gamma, 3.0);
+        assert_close(adjustments.gamma, 1.1f32.powi(3));
+        adjustments.step(Adjustment::Gamma, -3.0);
+        assert_close(adjustments.gamma, 1.0);
+    }
+
+    #[test]
+    fn uniform_carries_every_value() {
+        let adjustments = Adjustments {
+            brightness: 0.25,
+            contrast: 1.5,
+            gamma: 2.0,
+            saturation: 0.5,
+            invert: true,
+        };
+        let uniform = adjustments.uniform();
+        assert_eq!(
+            [
+                uniform.brightness,
+                uniform.contrast,
+                uniform.gamma,
+                uniform.saturation
+            ],
+            [0.25, 1.5, 2.0, 0.5]
+        );
+        assert_eq!(uniform.invert, 1);
+        // the shader reads two 16-byte rows
+        assert_eq!(std::mem::size_of::<AdjustUniform>(), 32);
+    }
+
+    #[test]
+    fn next_visits_every_adjustment() {
+        let mut which = Adjustment::default();
+        for expected in Adjustment::ALL {
+            assert_eq!(which, expected);
+            which = which.next();
+        }
+        assert_eq!(which, Adjustment::default());
+    }
+}
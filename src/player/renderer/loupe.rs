@@ -0,0 +1,93 @@
+use super::view::View;
+use serde::Deserialize;
+use ultraviolet::{Mat4, Vec2, Vec3};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LoupeShape {
+    #[default]
+    Circle,
+    Square,
+}
+
+/// An inset showing the image around a point at a higher zoom.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Loupe {
+    /// Center on the surface, in screen pixels.
+    pub center: Vec2,
+    /// Half the width of the inset in screen pixels.
+    pub radius: f32,
+    /// Scale relative to the view.
+    pub zoom: f32,
+    pub shape: LoupeShape,
+}
+
+impl LoupeShape {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Circle => Self::Square,
+            Self::Square => Self::Circle,
+        }
+    }
+}
+
+impl Loupe {
+    /// Image to clip space inside the loupe, for a surface showing `view`.
+    pub fn transform(&self, view: &View, surface: Vec2) -> Mat4 {
+        let loupe = View {
+            center: view.screen_to_image(self.center, surface),
+            scale: view.scale * self.zoom,
+            ..*view
+        };
+        // `View` puts its center in the middle of the surface, move it under the loupe
+        let offset = View::screen_to_view(self.center, surface);
+        let offset = Vec3::new(offset.x / surface.x * 2.0, -offset.y / surface.y * 2.0, 0.0);
+        Mat4::from_translation(offset) * loupe.transform(surface)
+    }
+
+    /// The square around the loupe clipped to a pane, as x, y, width and height.
+    pub fn scissor(&self, origin: (u32, u32), size: (u32, u32)) -> Option<(u32, u32, u32, u32)> {
+        let clamp = |v: f32, min: u32, len: u32| v.clamp(min as f32, (min + len) as f32) as u32;
+        let left = clamp(self.center.x - self.radius, origin.0, size.0);
+        let top = clamp(self.center.y - self.radius, origin.1, size.1);
+        let right = clamp((self.center.x + self.radius).ceil(), origin.0, size.0);
+        let bottom = clamp((self.center.y + self.radius).ceil(), origin.1, size.1);
+        (right > left && bottom > top).then(|| (left, top, right - left, bottom - top))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ultraviolet::Vec4;
+
+    #[test]
+    fn loupe_magnifies_around_its_center() {
+        let surface = Vec2::new(800.0, 600.0);
+        let view = View {
+            scale: 0.5,
+            rotation: 0.3,
+            ..View::centered(Vec2::new(1000.0, 1000.0))
+        };
+        let loupe = Loupe {
+            center: Vec2::new(100.0, 450.0),
+            radius: 50.0,
+            zoom: 4.0,
+            shape: LoupeShape::Circle,
+        };
+        let to_screen = |p: Vec2| {
+            let clip = loupe.transform(&view, surface) * Vec4::new(p.x, p.y, 0.0, 1.0);
+            Vec2::new(
+                (clip.x + 1.0) * surface.x / 2.0,
+                (1.0 - clip.y) * surface.y / 2.0,
+            )
+        };
+        let under = view.screen_to_image(loupe.center, surface);
+        let center = to_screen(under);
+        assert!((center - loupe.center).mag() < 1e-2);
+        // a step in the image is four times longer in the loupe than in the view
+        let step = Vec2::new(3.0, 0.0);
+        let shown = view.image_to_screen(under + step, surface) - loupe.center;
+        assert!((to_screen(under + step) - center - shown * 4.0).mag() < 1e-2);
+    }
+}
@@ -0,0 +1,104 @@
+use super::texture::Texture;
+use std::collections::HashMap;
+use wgpu::include_wgsl;
+
+/// Fills the mip chain of textures by repeatedly halving the previous level on the GPU.
+pub struct MipGenerator {
+    pipelines: HashMap<wgpu::TextureFormat, wgpu::RenderPipeline>,
+    sampler: wgpu::Sampler,
+}
+
+impl MipGenerator {
+    /// Prepares a pipeline for each texture format that will be passed to `generate`.
+    pub fn new(device: &wgpu::Device, formats: &[wgpu::TextureFormat]) -> Self {
+        let shader = device.create_shader_module(&include_wgsl!("../../../shaders/blit.wgsl"));
+        let pipelines = formats
+            .iter()
+            .map(|&format| {
+                let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some("yume mipmap pipeline"),
+                    layout: None,
+                    vertex: wgpu::VertexState {
+                        module: &shader,
+                        entry_point: "vs_main",
+                        buffers: &[],
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: &shader,
+                        entry_point: "fs_main",
+                        targets: &[format.into()],
+                    }),
+                    primitive: wgpu::PrimitiveState::default(),
+                    depth_stencil: None,
+                    multisample: wgpu::MultisampleState::default(),
+                    multiview: None,
+                });
+                (format, pipeline)
+            })
+            .collect();
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("yume mipmap sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self { pipelines, sampler }
+    }
+
+    /// Regenerates every level below the first one.
+    pub fn generate(&self, device: &wgpu::Device, queue: &wgpu::Queue, texture: &Texture) {
+        let levels = texture.mip_level_count;
+        if levels < 2 {
+            return;
+        }
+        let pipeline = &self.pipelines[&texture.format];
+        let layout = pipeline.get_bind_group_layout(0);
+        let views: Vec<_> = (0..levels)
+            .map(|level| {
+                texture.texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("yume mipmap view"),
+                    base_mip_level: level,
+                    mip_level_count: std::num::NonZeroU32::new(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("yume mipmap encoder"),
+        });
+        for pair in views.windows(2) {
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&pair[0]),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                ],
+                label: Some("yume mipmap bind group"),
+            });
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("yume mipmap pass"),
+                color_attachments: &[wgpu::RenderPassColorAttachment {
+                    view: &pair[1],
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: None,
+            });
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+        queue.submit(std::iter::once(encoder.finish()));
+    }
+}
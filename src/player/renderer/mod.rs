@@ -1,6 +1,22 @@
+pub mod adjust;
+pub mod loupe;
+pub mod mipmap;
+pub mod pane;
+pub mod text;
 pub mod texture;
+pub mod thumbnails;
+pub mod tile;
+pub mod view;
 
+use super::thumbnail;
+use adjust::Adjustments;
 use bytemuck::{Pod, Zeroable};
+use loupe::{Loupe, LoupeShape};
+use pane::Pane;
+use serde::Deserialize;
+use std::collections::HashMap;
+use ultraviolet::Vec2;
+use view::View;
 use wgpu::{include_wgsl, util::DeviceExt, Backends, Instance};
 use winit::{dpi::PhysicalSize, window::Window};
 
@@ -10,10 +26,59 @@ const INDICES: &[u16] = &[
     0, 1, 2
 ];
 
+/// How colors outside of the displayable range are brought back into it.
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
-pub struct Pan {
-    pub width: i32,
-    pub height: i32,
+pub enum Tonemap {
+    #[default]
+    Clamp,
+    Reinhard,
+    Aces,
+}
+
+/// How the image is resampled when it is scaled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Filter {
+    Nearest,
+    /// Trilinear when zoomed out.
+    #[default]
+    Bilinear,
+    Bicubic,
+    Lanczos,
+}
+
+/// What is drawn behind transparent parts of the image.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Background {
+    #[default]
+    Checkerboard,
+    /// Only the background color.
+    Solid,
+    /// The image itself, blurred to fill the window.
+    Blur,
+}
+
+/// Which part of the colors is shown.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Channels {
+    #[default]
+    Color,
+    Red,
+    Green,
+    Blue,
+    Alpha,
+    /// Opaque colors, tinted where they are transparent.
+    AlphaOverlay,
+}
+
+/// How the first two images are drawn on top of each other instead of side by side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    /// The current image left of a movable line, the other one right of it.
+    Wipe,
+    /// How far apart the two images are, as a heatmap.
+    Difference,
 }
 
 pub struct Renderer {
@@ -22,39 +87,192 @@ pub struct Renderer {
     pub queue: wgpu::Queue,
     pub config: wgpu::SurfaceConfiguration,
     pub size: winit::dpi::PhysicalSize<u32>,
-    pub pan: Pan,
-    pub scale: f32,
+    pub view: View,
     pub render_pipeline: wgpu::RenderPipeline,
-    pub vertex_buffer: wgpu::Buffer,
+    /// Variants of `render_pipeline` for the other `Channels`.
+    channel_pipelines: HashMap<Channels, wgpu::RenderPipeline>,
+    checker_pipeline: wgpu::RenderPipeline,
+    blur_pipeline: wgpu::RenderPipeline,
+    /// Binds the tiles of the other image as group 2.
+    difference_pipeline: wgpu::RenderPipeline,
+    wipe_pipeline: wgpu::RenderPipeline,
     pub index_buffer: wgpu::Buffer,
     num_indices: u32,
     texture_bind_group_layout: wgpu::BindGroupLayout,
-    bind_group: wgpu::BindGroup,
-    texture: texture::Texture,
+    /// The current image first, then the images it is compared with.
+    panes: Vec<Pane>,
+    mipmaps: mipmap::MipGenerator,
+    /// Format of textures with more than 8 bits per channel.
+    float_format: wgpu::TextureFormat,
+    display_buffer: wgpu::Buffer,
+    display_bind_group: wgpu::BindGroup,
+    /// Display settings for the loupe, bound in place of the display buffer.
+    loupe_buffer: wgpu::Buffer,
+    loupe_bind_group: wgpu::BindGroup,
+    loupe_frame_pipeline: wgpu::RenderPipeline,
+    loupe_pipeline: wgpu::RenderPipeline,
+    loupe: Option<Loupe>,
+    adjust_buffer: wgpu::Buffer,
+    adjustments: Adjustments,
+    exposure: f32,
+    tonemap: Tonemap,
+    filter: Filter,
+    nearest_when_magnified: bool,
+    pixel_perfect: bool,
+    background: Background,
+    channels: Channels,
+    premultiplied: bool,
+    /// sRGB, also shown around the image.
+    background_color: [u8; 3],
+    checker_size: f32,
+    checker_colors: [[u8; 3]; 2],
+    comparison: Option<Comparison>,
+    /// Position of the wipe line as a fraction of the surface width.
+    wipe: f32,
+    /// Show the other image in place of the current one.
+    flicker: bool,
+    /// Factor the difference is multiplied by before it is shown.
+    amplification: f32,
+    /// Draw `thumbnails` instead of the images.
+    gallery: bool,
+    pub thumbnails: thumbnails::ThumbnailLayer,
+    pub text: text::TextLayer,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct DisplayUniform {
+    transform: [f32; 16],
+    exposure: f32,
+    tonemap: u32,
+    filter: u32,
+    scale: f32,
+    background_transform: [f32; 16],
+    checker_size: f32,
+    premultiplied: u32,
+    _padding: [f32; 2],
+    checker_a: [f32; 4],
+    checker_b: [f32; 4],
+    loupe: [f32; 4],
+    surface: [f32; 2],
+    origin: [f32; 2],
+    amplification: f32,
+    _padding_end: [f32; 3],
 }
 
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Pod, Zeroable)]
 pub struct Vertex {
-    position: [f32; 3],
+    position: [f32; 2],
     tex_coords: [f32; 2],
 }
 
-impl Pan {
-    pub fn increase_width(&mut self, v: i32) {
-        self.width += v;
+impl Tonemap {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Clamp => Self::Reinhard,
+            Self::Reinhard => Self::Aces,
+            Self::Aces => Self::Clamp,
+        }
+    }
+}
+
+impl Filter {
+    pub const ALL: &'static [Self] = &[Self::Nearest, Self::Bilinear, Self::Bicubic, Self::Lanczos];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Nearest => "nearest",
+            Self::Bilinear => "bilinear",
+            Self::Bicubic => "bicubic",
+            Self::Lanczos => "lanczos",
+        }
     }
-    pub fn increase_height(&mut self, v: i32) {
-        self.height += v;
+
+    pub fn next(self) -> Self {
+        match self {
+            Self::Nearest => Self::Bilinear,
+            Self::Bilinear => Self::Bicubic,
+            Self::Bicubic => Self::Lanczos,
+            Self::Lanczos => Self::Nearest,
+        }
+    }
+}
+
+impl Channels {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Color => Self::Red,
+            Self::Red => Self::Green,
+            Self::Green => Self::Blue,
+            Self::Blue => Self::Alpha,
+            Self::Alpha => Self::AlphaOverlay,
+            Self::AlphaOverlay => Self::Color,
+        }
+    }
+
+    fn entry_point(self) -> &'static str {
+        match self {
+            Self::Color => "fs_main",
+            Self::Red => "fs_red",
+            Self::Green => "fs_green",
+            Self::Blue => "fs_blue",
+            Self::Alpha => "fs_alpha",
+            Self::AlphaOverlay => "fs_alpha_overlay",
+        }
+    }
+}
+
+impl Background {
+    pub const ALL: &'static [Self] = &[Self::Checkerboard, Self::Solid, Self::Blur];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Checkerboard => "checkerboard",
+            Self::Solid => "solid",
+            Self::Blur => "blur",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Self::Checkerboard => Self::Solid,
+            Self::Solid => Self::Blur,
+            Self::Blur => Self::Checkerboard,
+        }
+    }
+}
+
+impl std::str::FromStr for Background {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .find(|background| background.name() == s)
+            .copied()
+            .ok_or_else(|| format!("unknown background {}", s))
+    }
+}
+
+impl std::str::FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .find(|filter| filter.name() == s)
+            .copied()
+            .ok_or_else(|| format!("unknown filter {}", s))
     }
 }
 
 impl Renderer {
     pub async fn idle(window: &Window) -> Self {
-        Self::new(window, &texture::Rgba8Image::new(1, 1)).await
+        Self::new(window, &texture::Rgba8Image::new(1, 1).into()).await
     }
 
-    pub async fn new(window: &Window, img: &texture::Rgba8Image) -> Self {
+    pub async fn new(window: &Window, img: &texture::Pixels) -> Self {
         let size = window.inner_size();
 
         let instance = Instance::new(Backends::all());
@@ -67,10 +285,25 @@ impl Renderer {
             })
             .await
             .unwrap();
+        // 32-bit float textures are only used where they can be filtered, half floats otherwise
+        let float32_filterable = adapter
+            .features()
+            .contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES)
+            && adapter
+                .get_texture_format_features(wgpu::TextureFormat::Rgba32Float)
+                .filterable;
+        let (features, float_format) = if float32_filterable {
+            (
+                wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES,
+                wgpu::TextureFormat::Rgba32Float,
+            )
+        } else {
+            (wgpu::Features::empty(), wgpu::TextureFormat::Rgba16Float)
+        };
         let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
-                    features: wgpu::Features::ADDRESS_MODE_CLAMP_TO_BORDER,
+                    features,
                     limits: Default::default(),
                     label: None,
                 },
@@ -87,154 +320,388 @@ impl Renderer {
         };
         surface.configure(&device, &config);
 
-        let texture = texture::Texture::from_image(&device, &queue, img, Some("yume texture"));
-
         let texture_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                }],
+                label: Some("yume texture bind group layout"),
+            });
+        let mipmaps = mipmap::MipGenerator::new(
+            &device,
+            &[wgpu::TextureFormat::Rgba8UnormSrgb, float_format],
+        );
+        let pane = Pane::new(
+            &device,
+            &queue,
+            &texture_bind_group_layout,
+            &mipmaps,
+            img,
+            float_format,
+        );
+
+        let display_buffer = |label| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size: std::mem::size_of::<DisplayUniform>() as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        };
+        let loupe_buffer = display_buffer("yume loupe buffer");
+        let display_buffer = display_buffer("yume display buffer");
+        let adjustments = Adjustments::default();
+        let adjust_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("yume adjust buffer"),
+            contents: bytemuck::bytes_of(&adjustments.uniform()),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let sampler = |filter| {
+            device.create_sampler(&wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                mag_filter: filter,
+                min_filter: filter,
+                mipmap_filter: filter,
+                ..Default::default()
+            })
+        };
+        let linear_sampler = sampler(wgpu::FilterMode::Linear);
+        let nearest_sampler = sampler(wgpu::FilterMode::Nearest);
+        let sampler_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        };
+        let display_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 entries: &[
                     wgpu::BindGroupLayoutEntry {
                         binding: 0,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                            multisampled: false,
+                        visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
                         },
                         count: None,
                     },
+                    sampler_entry(1),
+                    sampler_entry(2),
                     wgpu::BindGroupLayoutEntry {
-                        binding: 1,
+                        binding: 3,
                         visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
                         count: None,
                     },
                 ],
-                label: Some("yume texture bind group layout"),
+                label: Some("yume display bind group layout"),
             });
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &texture_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&texture.view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
-                },
-            ],
-            label: Some("yume diffuse bind group"),
-        });
+        let display_bind_group = |label, buffer: &wgpu::Buffer| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &display_bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&linear_sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::Sampler(&nearest_sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: adjust_buffer.as_entire_binding(),
+                    },
+                ],
+                label: Some(label),
+            })
+        };
+        let loupe_bind_group = display_bind_group("yume loupe bind group", &loupe_buffer);
+        let display_bind_group = display_bind_group("yume display bind group", &display_buffer);
 
         let shader = device.create_shader_module(&include_wgsl!("../../../shaders/shader.wgsl"));
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("yume pipeline layout"),
-            bind_group_layouts: &[&texture_bind_group_layout],
+            bind_group_layouts: &[&texture_bind_group_layout, &display_bind_group_layout],
             push_constant_ranges: &[],
         });
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("yume pipeline"),
-            layout: Some(&pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[Vertex::desc()],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                }],
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                unclipped_depth: false,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
-        });
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("yume vertex buffer"),
-            contents: bytemuck::cast_slice(&Vertex::compute(
-                img.dimensions(),
-                (size.width, size.height),
-                Pan::default(),
-                1.0,
-            )),
-            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        let difference_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("yume difference pipeline layout"),
+            bind_group_layouts: &[
+                &texture_bind_group_layout,
+                &display_bind_group_layout,
+                &texture_bind_group_layout,
+            ],
+            push_constant_ranges: &[],
         });
+        // every pipeline draws the tiles, with different shader entry points
+        let pipeline_in = |layout, label, vertex, fragment, blend| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: vertex,
+                    buffers: &[Vertex::desc()],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: fragment,
+                    targets: &[wgpu::ColorTargetState {
+                        format: config.format,
+                        blend: Some(blend),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    }],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    // flipped views turn the quads around
+                    cull_mode: None,
+                    unclipped_depth: false,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    conservative: false,
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None,
+            })
+        };
+        let pipeline = |label, vertex, fragment, blend| {
+            pipeline_in(&pipeline_layout, label, vertex, fragment, blend)
+        };
+        let render_pipeline = pipeline(
+            "yume pipeline",
+            "vs_main",
+            "fs_main",
+            wgpu::BlendState::ALPHA_BLENDING,
+        );
+        let channel_pipelines = [
+            Channels::Red,
+            Channels::Green,
+            Channels::Blue,
+            Channels::Alpha,
+            Channels::AlphaOverlay,
+        ]
+        .into_iter()
+        .map(|channels| {
+            let pipeline = pipeline(
+                "yume channel pipeline",
+                "vs_main",
+                channels.entry_point(),
+                wgpu::BlendState::ALPHA_BLENDING,
+            );
+            (channels, pipeline)
+        })
+        .collect();
+        let checker_pipeline = pipeline(
+            "yume checkerboard pipeline",
+            "vs_main",
+            "fs_checker",
+            wgpu::BlendState::REPLACE,
+        );
+        let blur_pipeline = pipeline(
+            "yume blur pipeline",
+            "vs_background",
+            "fs_blur",
+            wgpu::BlendState::REPLACE,
+        );
+        let loupe_frame_pipeline = pipeline(
+            "yume loupe frame pipeline",
+            "vs_loupe_frame",
+            "fs_loupe_frame",
+            wgpu::BlendState::REPLACE,
+        );
+        let loupe_pipeline = pipeline(
+            "yume loupe pipeline",
+            "vs_main",
+            "fs_loupe",
+            wgpu::BlendState::ALPHA_BLENDING,
+        );
+        let difference_pipeline = pipeline_in(
+            &difference_layout,
+            "yume difference pipeline",
+            "vs_main",
+            "fs_difference",
+            wgpu::BlendState::REPLACE,
+        );
+        let wipe_pipeline = pipeline(
+            "yume wipe pipeline",
+            "vs_fullscreen",
+            "fs_wipe",
+            wgpu::BlendState::REPLACE,
+        );
         let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("yume index buffer"),
             contents: bytemuck::cast_slice(INDICES),
             usage: wgpu::BufferUsages::INDEX,
         });
 
-        Self {
+        let text =
+            text::TextLayer::new(&device, &queue, config.format, window.scale_factor() as f32);
+        let thumbnails =
+            thumbnails::ThumbnailLayer::new(&device, &queue, config.format, thumbnail::SIZE);
+
+        let mut renderer = Self {
             surface,
             device,
             queue,
             config,
             size,
-            pan: Pan::default(),
-            scale: 1.0,
+            view: View::centered(Vec2::new(
+                img.dimensions().0 as f32,
+                img.dimensions().1 as f32,
+            )),
             render_pipeline,
-            vertex_buffer,
+            channel_pipelines,
+            checker_pipeline,
+            blur_pipeline,
+            difference_pipeline,
+            wipe_pipeline,
             index_buffer,
             num_indices: INDICES.len() as u32,
             texture_bind_group_layout,
-            bind_group,
-            texture,
+            panes: vec![pane],
+            mipmaps,
+            float_format,
+            display_buffer,
+            display_bind_group,
+            loupe_buffer,
+            loupe_bind_group,
+            loupe_frame_pipeline,
+            loupe_pipeline,
+            loupe: None,
+            adjust_buffer,
+            adjustments,
+            exposure: 0.0,
+            tonemap: Tonemap::default(),
+            filter: Filter::default(),
+            nearest_when_magnified: false,
+            pixel_perfect: false,
+            background: Background::default(),
+            channels: Channels::default(),
+            premultiplied: false,
+            background_color: [0, 0, 0],
+            checker_size: 8.0,
+            checker_colors: [[204, 204, 204], [153, 153, 153]],
+            comparison: None,
+            wipe: 0.5,
+            flicker: false,
+            amplification: 1.0,
+            gallery: false,
+            thumbnails,
+            text,
+        };
+        renderer.update_view();
+        renderer
+    }
+
+    fn pane(&self, img: &texture::Pixels) -> Pane {
+        Pane::new(
+            &self.device,
+            &self.queue,
+            &self.texture_bind_group_layout,
+            &self.mipmaps,
+            img,
+            self.float_format,
+        )
+    }
+
+    /// Whether `img` is shown with less precision than it has, which happens to 16-bit images
+    /// where only half float textures can be filtered.
+    pub fn loses_precision(&self, img: &texture::Pixels) -> bool {
+        matches!(img, texture::Pixels::Rgba16(_))
+            && self.float_format == wgpu::TextureFormat::Rgba16Float
+    }
+
+    pub fn update_image(&mut self, img: &texture::Pixels) {
+        self.panes[0] = self.pane(img);
+        self.update_view();
+    }
+
+    /// Shows another frame of the current image, reusing the textures when possible.
+    pub fn update_frame(&mut self, img: &texture::Pixels) {
+        if self.panes[0].fits(img, self.float_format) {
+            self.panes[0].write(&self.device, &self.queue, &self.mipmaps, img);
+        } else {
+            self.update_image(img);
         }
     }
 
-    pub fn update_image(&mut self, img: &texture::Rgba8Image) {
-        let texture =
-            texture::Texture::from_image(&self.device, &self.queue, img, Some("yume texture"));
+    /// Splits the surface to show `images` next to the current one, with the same view.
+    /// Up to three images are shown, none returns to a single pane.
+    pub fn set_compare(&mut self, images: &[&texture::Pixels]) {
+        self.panes.truncate(1);
+        for img in images.iter().take(3) {
+            let pane = self.pane(img);
+            self.panes.push(pane);
+        }
+        self.update_view();
+    }
 
-        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &self.texture_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&texture.view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
-                },
-            ],
-            label: Some("yume diffuse bind group"),
-        });
+    /// Number of panes the surface is split into.
+    pub fn panes(&self) -> usize {
+        self.panes.len()
+    }
 
-        self.texture = texture;
-        self.bind_group = bind_group;
-        self.reconfigure_vertex_buffer();
+    /// How the panes share the surface.
+    fn layout(&self) -> pane::Layout {
+        pane::Layout {
+            panes: self.panes.len(),
+            surface: self.size,
+            comparison: self.comparison,
+            flicker: self.flicker,
+            wipe: self.wipe,
+        }
     }
 
-    pub fn reconfigure_vertex_buffer(&mut self) {
-        self.queue.write_buffer(
-            &self.vertex_buffer,
-            0,
-            bytemuck::cast_slice(&Vertex::compute(
-                (self.texture.size.width, self.texture.size.height),
-                (self.size.width, self.size.height),
-                self.pan,
-                self.scale,
-            )),
-        );
+    /// Size of each pane, which `view` is relative to.
+    pub fn view_size(&self) -> PhysicalSize<u32> {
+        self.layout().pane_size()
+    }
+
+    /// Index of the pane at `p` in surface pixels.
+    pub fn pane_at(&self, p: Vec2) -> usize {
+        self.layout().pane_at(p)
+    }
+
+    /// Converts surface pixels to pixels of the pane they fall in, like `view` expects.
+    pub fn to_pane(&self, p: Vec2) -> Vec2 {
+        self.layout().to_pane(p)
+    }
+
+    /// Applies changes to `view` and works out which tiles are visible.
+    pub fn update_view(&mut self) {
+        let size = self.view_size();
+        let surface = Vec2::new(size.width as f32, size.height as f32);
+        self.view.clamp(self.panes[0].size(), surface);
+        let view = self.view;
+        for pane in &mut self.panes {
+            pane.update_visibility(&view, surface);
+        }
+        self.write_display_uniform();
     }
 
     pub fn resize(&mut self, size: winit::dpi::PhysicalSize<u32>) {
@@ -244,10 +711,16 @@ impl Renderer {
         self.config.width = size.width;
         self.config.height = size.height;
         self.surface.configure(&self.device, &self.config);
-        self.reconfigure_vertex_buffer();
+        self.update_view();
     }
 
-    pub fn render(&self) -> Result<(), wgpu::SurfaceError> {
+    pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+        self.text.prepare(&self.device, &self.queue, self.size);
+        if self.gallery {
+            self.thumbnails
+                .prepare(&self.device, &self.queue, self.size);
+        }
+
         let output = self.surface.get_current_texture()?;
         let view = output
             .texture
@@ -257,6 +730,7 @@ impl Renderer {
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
                 label: Some("yume render encoder"),
             });
+        let clear = linear_color(self.background_color);
 
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
@@ -266,9 +740,9 @@ impl Renderer {
                     resolve_target: None,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.0,
-                            g: 0.0,
-                            b: 0.0,
+                            r: clear[0] as f64,
+                            g: clear[1] as f64,
+                            b: clear[2] as f64,
                             a: 1.0,
                         }),
                         store: true,
@@ -277,11 +751,86 @@ impl Renderer {
                 depth_stencil_attachment: None,
             });
 
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_bind_group(0, &self.bind_group, &[]);
-            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
             render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-            render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
+            let layout = self.layout();
+            let size = layout.pane_size();
+            let loupe_pane = self.loupe.map(|loupe| layout.pane_at(loupe.center));
+            let difference = layout.overlaid() && self.comparison == Some(Comparison::Difference);
+            let rects = if self.gallery { vec![] } else { layout.rects() };
+            for (i, rect) in rects {
+                let pane = &self.panes[i];
+                let (x, y) = layout.origin(i);
+                render_pass.set_viewport(
+                    x as f32,
+                    y as f32,
+                    size.width as f32,
+                    size.height as f32,
+                    0.0,
+                    1.0,
+                );
+                render_pass.set_scissor_rect(rect.0, rect.1, rect.2, rect.3);
+                render_pass.set_bind_group(1, &self.display_bind_group, &[]);
+                render_pass.set_vertex_buffer(0, pane.vertex_buffer.slice(..));
+                match self.background {
+                    Background::Checkerboard => {
+                        render_pass.set_pipeline(&self.checker_pipeline);
+                        self.draw_tiles(&mut render_pass, pane, false);
+                    }
+                    Background::Solid => {}
+                    Background::Blur => {
+                        render_pass.set_pipeline(&self.blur_pipeline);
+                        // stretched over the whole pane, hidden tiles may show up
+                        self.draw_tiles(&mut render_pass, pane, true);
+                    }
+                }
+                if difference && i == 0 {
+                    render_pass.set_pipeline(&self.difference_pipeline);
+                    self.draw_difference(&mut render_pass, pane, &self.panes[1]);
+                } else {
+                    let pipeline = self
+                        .channel_pipelines
+                        .get(&self.channels)
+                        .unwrap_or(&self.render_pipeline);
+                    render_pass.set_pipeline(pipeline);
+                    self.draw_tiles(&mut render_pass, pane, false);
+                }
+
+                let scissor = match self.loupe {
+                    Some(loupe) if loupe_pane == Some(i) => {
+                        loupe.scissor((x, y), (size.width, size.height))
+                    }
+                    _ => None,
+                };
+                if let Some((x, y, width, height)) = scissor {
+                    render_pass.set_scissor_rect(x, y, width, height);
+                    render_pass.set_bind_group(1, &self.loupe_bind_group, &[]);
+                    render_pass.set_pipeline(&self.loupe_frame_pipeline);
+                    render_pass.draw(0..3, 0..1);
+                    render_pass.set_pipeline(&self.loupe_pipeline);
+                    // the loupe may show tiles that are off screen in the view
+                    self.draw_tiles(&mut render_pass, pane, true);
+                }
+            }
+            let (width, height) = (self.size.width, self.size.height);
+            render_pass.set_viewport(0.0, 0.0, width as f32, height as f32, 0.0, 1.0);
+            if self.gallery {
+                self.thumbnails.draw(&mut render_pass);
+            } else if layout.overlaid()
+                && !self.flicker
+                && self.comparison == Some(Comparison::Wipe)
+            {
+                let x = layout
+                    .wipe_x()
+                    .saturating_sub(1)
+                    .min(width.saturating_sub(2));
+                render_pass.set_scissor_rect(x, 0, 2.min(width), height);
+                render_pass.set_bind_group(1, &self.display_bind_group, &[]);
+                render_pass.set_pipeline(&self.wipe_pipeline);
+                render_pass.draw(0..3, 0..1);
+            }
+            render_pass.set_scissor_rect(0, 0, width, height);
+
+            self.text.draw(&mut render_pass);
         }
 
         self.queue.submit(std::iter::once(encoder.finish()));
@@ -290,31 +839,282 @@ impl Renderer {
         Ok(())
     }
 
+    fn draw_tiles<'a>(&self, render_pass: &mut wgpu::RenderPass<'a>, pane: &'a Pane, hidden: bool) {
+        for (i, tile) in pane.tiles.iter().enumerate() {
+            if tile.visible || hidden {
+                render_pass.set_bind_group(0, &tile.bind_group, &[]);
+                render_pass.draw_indexed(0..self.num_indices, i as i32 * 4, 0..1);
+            }
+        }
+    }
+
+    /// Draws `pane` with the tiles of `other` at the same place in the image bound as group 2.
+    fn draw_difference<'a>(
+        &self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        pane: &'a Pane,
+        other: &'a Pane,
+    ) {
+        for (i, tile) in pane.tiles.iter().enumerate() {
+            // parts of the image the other one doesn't reach are left out
+            let matching = other.tiles.iter().find(|t| t.origin == tile.origin);
+            if let (true, Some(matching)) = (tile.visible, matching) {
+                render_pass.set_bind_group(0, &tile.bind_group, &[]);
+                render_pass.set_bind_group(2, &matching.bind_group, &[]);
+                render_pass.draw_indexed(0..self.num_indices, i as i32 * 4, 0..1);
+            }
+        }
+    }
+
     pub fn surface_size(&self) -> PhysicalSize<u32> {
         self.size
     }
 
     pub fn texture_size(&self) -> PhysicalSize<u32> {
-        let size = self.texture.size;
-        PhysicalSize {
-            width: size.width,
-            height: size.height,
+        let (width, height) = self.panes[0].image_size;
+        PhysicalSize { width, height }
+    }
+
+    pub fn set_scale(&mut self, v: f32) {
+        self.view.scale = v.max(f32::EPSILON);
+        self.update_view();
+    }
+
+    /// Exposure adjustment in stops.
+    pub fn exposure(&self) -> f32 {
+        self.exposure
+    }
+
+    pub fn set_exposure(&mut self, v: f32) {
+        self.exposure = v;
+        self.write_display_uniform();
+    }
+
+    pub fn tonemap(&self) -> Tonemap {
+        self.tonemap
+    }
+
+    pub fn set_tonemap(&mut self, tonemap: Tonemap) {
+        self.tonemap = tonemap;
+        self.write_display_uniform();
+    }
+
+    pub fn filter(&self) -> Filter {
+        self.filter
+    }
+
+    pub fn set_filter(&mut self, filter: Filter) {
+        self.filter = filter;
+        self.write_display_uniform();
+    }
+
+    pub fn nearest_when_magnified(&self) -> bool {
+        self.nearest_when_magnified
+    }
+
+    /// Use nearest neighbour above 100% zoom, whatever the filter is.
+    pub fn set_nearest_when_magnified(&mut self, v: bool) {
+        self.nearest_when_magnified = v;
+        self.write_display_uniform();
+    }
+
+    pub fn pixel_perfect(&self) -> bool {
+        self.pixel_perfect
+    }
+
+    /// Sample with nearest neighbour and align image pixels to screen pixels.
+    /// Callers keep the scale whole, see `zoom::snap`.
+    pub fn set_pixel_perfect(&mut self, v: bool) {
+        self.pixel_perfect = v;
+        self.write_display_uniform();
+    }
+
+    pub fn adjustments(&self) -> Adjustments {
+        self.adjustments
+    }
+
+    pub fn set_adjustments(&mut self, v: Adjustments) {
+        self.adjustments = v;
+        self.queue
+            .write_buffer(&self.adjust_buffer, 0, bytemuck::bytes_of(&v.uniform()));
+    }
+
+    pub fn channels(&self) -> Channels {
+        self.channels
+    }
+
+    pub fn set_channels(&mut self, v: Channels) {
+        self.channels = v;
+    }
+
+    pub fn premultiplied(&self) -> bool {
+        self.premultiplied
+    }
+
+    /// Treat the color channels as already multiplied by alpha.
+    pub fn set_premultiplied(&mut self, v: bool) {
+        self.premultiplied = v;
+        self.write_display_uniform();
+    }
+
+    pub fn background(&self) -> Background {
+        self.background
+    }
+
+    pub fn set_background(&mut self, v: Background) {
+        self.background = v;
+    }
+
+    /// Solid background, also shown around the image whatever the background is.
+    pub fn set_background_color(&mut self, color: [u8; 3]) {
+        self.background_color = color;
+    }
+
+    /// Size of the squares in surface pixels and their sRGB colors.
+    pub fn set_checkerboard(&mut self, size: f32, colors: [[u8; 3]; 2]) {
+        self.checker_size = size.max(1.0);
+        self.checker_colors = colors;
+        self.write_display_uniform();
+    }
+
+    /// `view` as drawn, which may be moved by a fraction of a pixel.
+    pub fn shown_view(&self) -> View {
+        if self.pixel_perfect {
+            let size = self.view_size();
+            let surface = Vec2::new(size.width as f32, size.height as f32);
+            self.view.pixel_aligned(surface)
+        } else {
+            self.view
         }
     }
 
-    pub fn add_scale(&mut self, d: f32) {
-        self.set_scale(self.scale + d);
+    pub fn comparison(&self) -> Option<Comparison> {
+        self.comparison
     }
 
-    pub fn set_scale(&mut self, v: f32) {
-        self.scale = v.max(f32::EPSILON);
-        self.reconfigure_vertex_buffer();
+    /// Draws the first two images on top of each other, or side by side with `None`.
+    pub fn set_comparison(&mut self, v: Option<Comparison>) {
+        self.comparison = v;
+        self.update_view();
+    }
+
+    pub fn wipe(&self) -> f32 {
+        self.wipe
+    }
+
+    pub fn set_wipe(&mut self, v: f32) {
+        self.wipe = v.clamp(0.0, 1.0);
+    }
+
+    pub fn flicker(&self) -> bool {
+        self.flicker
+    }
+
+    pub fn set_flicker(&mut self, v: bool) {
+        if self.flicker != v {
+            self.flicker = v;
+            self.update_view();
+        }
+    }
+
+    pub fn amplification(&self) -> f32 {
+        self.amplification
+    }
+
+    pub fn set_amplification(&mut self, v: f32) {
+        self.amplification = v;
+        self.write_display_uniform();
+    }
+
+    pub fn gallery(&self) -> bool {
+        self.gallery
+    }
+
+    pub fn set_gallery(&mut self, v: bool) {
+        self.gallery = v;
+    }
+
+    pub fn loupe(&self) -> Option<Loupe> {
+        self.loupe
+    }
+
+    pub fn set_loupe(&mut self, v: Option<Loupe>) {
+        self.loupe = v;
+        self.write_display_uniform();
+    }
+
+    fn write_display_uniform(&self) {
+        let magnified = self.nearest_when_magnified && self.view.scale > 1.0;
+        let filter = if magnified || self.pixel_perfect {
+            Filter::Nearest
+        } else {
+            self.filter
+        };
+        let surface = self.view_size();
+        let surface = Vec2::new(surface.width as f32, surface.height as f32);
+        let view = self.shown_view();
+        let size = self.panes[0].size();
+        let bounds = view.bounds(size);
+        let cover = View {
+            center: size / 2.0,
+            scale: (surface.x / bounds.x).max(surface.y / bounds.y),
+            ..view
+        };
+        let loupe = self.loupe.unwrap_or(Loupe {
+            center: Vec2::zero(),
+            radius: 0.0,
+            zoom: 1.0,
+            shape: Default::default(),
+        });
+        let layout = self.layout();
+        let origin = layout.origin(layout.pane_at(loupe.center));
+        let origin = Vec2::new(origin.0 as f32, origin.1 as f32);
+        let uniform = DisplayUniform {
+            transform: *view.transform(surface).as_array(),
+            exposure: self.exposure,
+            tonemap: self.tonemap as u32,
+            filter: filter as u32,
+            scale: self.view.scale,
+            background_transform: *cover.transform(surface).as_array(),
+            checker_size: self.checker_size,
+            premultiplied: self.premultiplied as u32,
+            _padding: [0.0; 2],
+            checker_a: linear_color(self.checker_colors[0]),
+            checker_b: linear_color(self.checker_colors[1]),
+            loupe: [
+                loupe.center.x,
+                loupe.center.y,
+                loupe.radius,
+                (loupe.shape == LoupeShape::Square) as u32 as f32,
+            ],
+            surface: [surface.x, surface.y],
+            origin: [origin.x, origin.y],
+            amplification: self.amplification,
+            _padding_end: [0.0; 3],
+        };
+        self.queue
+            .write_buffer(&self.display_buffer, 0, bytemuck::bytes_of(&uniform));
+        if self.loupe.is_some() {
+            // the loupe is drawn in the pane under it, whose viewport starts at `origin`
+            let local = Loupe {
+                center: loupe.center - origin,
+                ..loupe
+            };
+            let uniform = DisplayUniform {
+                transform: *local.transform(&view, surface).as_array(),
+                filter: Filter::Nearest as u32,
+                scale: view.scale * loupe.zoom,
+                ..uniform
+            };
+            self.queue
+                .write_buffer(&self.loupe_buffer, 0, bytemuck::bytes_of(&uniform));
+        }
     }
 }
 
 impl Vertex {
     const ATTRIBS: [wgpu::VertexAttribute; 2] =
-        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2];
+        wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2];
 
     pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
         wgpu::VertexBufferLayout {
@@ -324,36 +1124,85 @@ impl Vertex {
         }
     }
 
-    // (width, height)
-    pub fn compute(src: (u32, u32), dst: (u32, u32), pan: Pan, scale: f32) -> Vec<Self> {
-        let src = (src.0 as f32 * scale, src.1 as f32 * scale);
-        let width = dst.0 as f32 / src.0;
-        let height = dst.1 as f32 / src.1;
-        let mut pan_width = pan.width as f32 / src.0;
-        let mut pan_height = pan.height as f32 / src.1;
-        if width > 1.0 {
-            pan_width -= (dst.0 as f32 - src.0) / (2.0 * src.0);
-        }
-        if height > 1.0 {
-            pan_height -= (dst.1 as f32 - src.1) / (2.0 * src.1);
-        }
-        vec![
+    /// Corners of `tile` in image pixels, in the order of `INDICES`.
+    pub fn quad(tile: &tile::Tile) -> [Self; 4] {
+        let (left, top) = (tile.origin.0 as f32, tile.origin.1 as f32);
+        let right = left + tile.size.0 as f32;
+        let bottom = top + tile.size.1 as f32;
+        // the texture reaches into the neighbouring tiles
+        let ([u0, v0], [u1, v1]) = tile.tex_coords();
+        [
             Vertex {
-                position: [1.0, 1.0, 0.0],
-                tex_coords: [width + pan_width, pan_height],
+                position: [right, top],
+                tex_coords: [u1, v0],
             },
             Vertex {
-                position: [-1.0, 1.0, 0.0],
-                tex_coords: [pan_width, pan_height],
+                position: [left, top],
+                tex_coords: [u0, v0],
             },
             Vertex {
-                position: [-1.0, -1.0, 0.0],
-                tex_coords: [pan_width, height + pan_height],
+                position: [left, bottom],
+                tex_coords: [u0, v1],
             },
             Vertex {
-                position: [1.0, -1.0, 0.0],
-                tex_coords: [width + pan_width, height + pan_height],
+                position: [right, bottom],
+                tex_coords: [u1, v1],
             },
         ]
     }
 }
+
+/// Linear RGBA of an opaque sRGB color, for the surface which converts back.
+fn linear_color(color: [u8; 3]) -> [f32; 4] {
+    let [r, g, b] = color.map(|c| texture::srgb_to_linear(c as f32 / 255.0));
+    [r, g, b, 1.0]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn names_parse_back() {
+        for &filter in Filter::ALL {
+            assert_eq!(filter.name().parse(), Ok(filter));
+        }
+        for &background in Background::ALL {
+            assert_eq!(background.name().parse(), Ok(background));
+        }
+        assert!("linear".parse::<Filter>().is_err());
+        assert!("none".parse::<Background>().is_err());
+    }
+
+    #[test]
+    fn next_visits_every_channel_view() {
+        let mut channels = Channels::default();
+        let mut seen = HashSet::new();
+        while seen.insert(channels) {
+            channels = channels.next();
+        }
+        assert_eq!(channels, Channels::Color);
+        assert_eq!(seen.len(), 6);
+    }
+
+    #[test]
+    fn channel_views_have_their_own_shaders() {
+        let shader = include_str!("../../../shaders/shader.wgsl");
+        let mut entry_points = HashSet::new();
+        let mut channels = Channels::default();
+        loop {
+            let entry_point = channels.entry_point();
+            assert!(
+                shader.contains(&format!("fn {}(", entry_point)),
+                "{}",
+                entry_point
+            );
+            assert!(entry_points.insert(entry_point), "{}", entry_point);
+            channels = channels.next();
+            if channels == Channels::default() {
+                break;
+            }
+        }
+    }
+}
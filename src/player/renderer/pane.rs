@@ -0,0 +1,276 @@
+use super::{mipmap::MipGenerator, texture::Pixels, tile::Tile, view::View, Comparison, Vertex};
+use ultraviolet::Vec2;
+use wgpu::util::DeviceExt;
+use winit::dpi::PhysicalSize;
+
+/// Part of the surface as x, y, width and height.
+pub type Rect = (u32, u32, u32, u32);
+
+/// An image and its textures, drawn in one part of the surface.
+pub struct Pane {
+    pub tiles: Vec<Tile>,
+    pub vertex_buffer: wgpu::Buffer,
+    pub image_size: (u32, u32),
+}
+
+impl Pane {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        mipmaps: &MipGenerator,
+        img: &Pixels,
+        float_format: wgpu::TextureFormat,
+    ) -> Self {
+        let tiles = Tile::split(device, queue, layout, mipmaps, img, float_format);
+        let vertices: Vec<Vertex> = tiles.iter().flat_map(Vertex::quad).collect();
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("yume vertex buffer"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        Self {
+            tiles,
+            vertex_buffer,
+            image_size: img.dimensions(),
+        }
+    }
+
+    /// Whether `img` can be written into the existing textures.
+    pub fn fits(&self, img: &Pixels, float_format: wgpu::TextureFormat) -> bool {
+        img.dimensions() == self.image_size
+            && self
+                .tiles
+                .iter()
+                .all(|tile| tile.texture.format == img.texture_format(float_format))
+    }
+
+    pub fn write(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        mipmaps: &MipGenerator,
+        img: &Pixels,
+    ) {
+        for tile in &self.tiles {
+            tile.write(device, queue, mipmaps, img);
+        }
+    }
+
+    pub fn size(&self) -> Vec2 {
+        Vec2::new(self.image_size.0 as f32, self.image_size.1 as f32)
+    }
+
+    /// Works out which tiles `view` shows on a surface of `surface` pixels.
+    pub fn update_visibility(&mut self, view: &View, surface: Vec2) {
+        for tile in &mut self.tiles {
+            let (x, y) = (tile.origin.0 as f32, tile.origin.1 as f32);
+            let (width, height) = (tile.size.0 as f32, tile.size.1 as f32);
+            let corners = [
+                Vec2::new(x, y),
+                Vec2::new(x + width, y),
+                Vec2::new(x, y + height),
+                Vec2::new(x + width, y + height),
+            ]
+            .map(|p| view.image_to_screen(p, surface));
+            let min = corners
+                .iter()
+                .fold(corners[0], |a, &b| a.min_by_component(b));
+            let max = corners
+                .iter()
+                .fold(corners[0], |a, &b| a.max_by_component(b));
+            tile.visible = min.x < surface.x && min.y < surface.y && max.x > 0.0 && max.y > 0.0;
+        }
+    }
+}
+
+/// How the panes share the surface.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Layout {
+    pub panes: usize,
+    pub surface: PhysicalSize<u32>,
+    pub comparison: Option<Comparison>,
+    /// Whether the second image is shown in place of the first.
+    pub flicker: bool,
+    /// Position of the wipe line as a fraction of the surface width.
+    pub wipe: f32,
+}
+
+impl Layout {
+    /// Whether the first two images share the whole surface instead of being side by side.
+    pub fn overlaid(&self) -> bool {
+        self.panes > 1 && (self.comparison.is_some() || self.flicker)
+    }
+
+    /// Columns and rows of panes.
+    pub fn grid(&self) -> (u32, u32) {
+        match self.panes {
+            _ if self.overlaid() => (1, 1),
+            0 | 1 => (1, 1),
+            2 => (2, 1),
+            _ => (2, 2),
+        }
+    }
+
+    /// Size of each pane, which the view is relative to.
+    pub fn pane_size(&self) -> PhysicalSize<u32> {
+        let (columns, rows) = self.grid();
+        PhysicalSize {
+            width: (self.surface.width / columns).max(1),
+            height: (self.surface.height / rows).max(1),
+        }
+    }
+
+    /// Top left corner of pane `i`.
+    pub fn origin(&self, i: usize) -> (u32, u32) {
+        if self.overlaid() {
+            return (0, 0);
+        }
+        let (columns, _) = self.grid();
+        let size = self.pane_size();
+        (
+            i as u32 % columns * size.width,
+            i as u32 / columns * size.height,
+        )
+    }
+
+    /// Index of the pane at `p` in surface pixels.
+    pub fn pane_at(&self, p: Vec2) -> usize {
+        if self.overlaid() {
+            let wiped = self.comparison == Some(Comparison::Wipe) && p.x >= self.wipe_x() as f32;
+            return (self.flicker || wiped) as usize;
+        }
+        let (columns, rows) = self.grid();
+        let size = self.pane_size();
+        let column = (p.x.max(0.0) as u32 / size.width).min(columns - 1);
+        let row = (p.y.max(0.0) as u32 / size.height).min(rows - 1);
+        ((row * columns + column) as usize).min(self.panes.max(1) - 1)
+    }
+
+    /// Converts surface pixels to pixels of the pane they fall in, like the view expects.
+    pub fn to_pane(&self, p: Vec2) -> Vec2 {
+        let (x, y) = self.origin(self.pane_at(p));
+        p - Vec2::new(x as f32, y as f32)
+    }
+
+    /// Pixel column of the wipe line.
+    pub fn wipe_x(&self) -> u32 {
+        ((self.wipe * self.surface.width as f32).round() as u32).min(self.surface.width)
+    }
+
+    /// Panes to draw and the part of the surface each one covers.
+    pub fn rects(&self) -> Vec<(usize, Rect)> {
+        let size = self.pane_size();
+        let (width, height) = (size.width, size.height);
+        if !self.overlaid() {
+            return (0..self.panes)
+                .map(|i| {
+                    let (x, y) = self.origin(i);
+                    (i, (x, y, width, height))
+                })
+                .collect();
+        }
+        if self.flicker {
+            return vec![(1, (0, 0, width, height))];
+        }
+        match self.comparison {
+            Some(Comparison::Wipe) => {
+                let x = self.wipe_x();
+                [(0, (0, 0, x, height)), (1, (x, 0, width - x, height))]
+                    .into_iter()
+                    .filter(|(_, (_, _, width, _))| *width > 0)
+                    .collect()
+            }
+            _ => vec![(0, (0, 0, width, height))],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(panes: usize) -> Layout {
+        Layout {
+            panes,
+            surface: PhysicalSize::new(800, 600),
+            comparison: None,
+            flicker: false,
+            wipe: 0.5,
+        }
+    }
+
+    #[test]
+    fn panes_tile_the_surface() {
+        for (panes, grid) in [(1, (1, 1)), (2, (2, 1)), (3, (2, 2)), (4, (2, 2))] {
+            let layout = layout(panes);
+            assert_eq!(layout.grid(), grid);
+            let size = layout.pane_size();
+            assert_eq!(size, PhysicalSize::new(800 / grid.0, 600 / grid.1));
+            for (i, rect) in layout.rects() {
+                assert_eq!((rect.0, rect.1), layout.origin(i));
+                // the center of each pane belongs to it
+                let center = Vec2::new((rect.0 + rect.2 / 2) as f32, (rect.1 + rect.3 / 2) as f32);
+                assert_eq!(layout.pane_at(center), i);
+                assert_eq!(
+                    layout.to_pane(center),
+                    Vec2::new((rect.2 / 2) as f32, (rect.3 / 2) as f32)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn points_outside_stay_in_existing_panes() {
+        let layout = layout(3);
+        assert_eq!(layout.pane_at(Vec2::new(-5.0, -5.0)), 0);
+        assert_eq!(layout.pane_at(Vec2::new(900.0, 10.0)), 1);
+        // the empty fourth cell
+        assert_eq!(layout.pane_at(Vec2::new(700.0, 500.0)), 2);
+    }
+
+    #[test]
+    fn wipe_splits_the_surface_at_the_line() {
+        let layout = Layout {
+            comparison: Some(Comparison::Wipe),
+            wipe: 0.25,
+            ..layout(2)
+        };
+        assert_eq!(layout.pane_size(), PhysicalSize::new(800, 600));
+        assert_eq!(
+            layout.rects(),
+            vec![(0, (0, 0, 200, 600)), (1, (200, 0, 600, 600))]
+        );
+        assert_eq!(layout.pane_at(Vec2::new(199.0, 300.0)), 0);
+        assert_eq!(layout.pane_at(Vec2::new(200.0, 300.0)), 1);
+        assert_eq!(layout.origin(1), (0, 0));
+
+        let edge = Layout {
+            wipe: 0.0,
+            ..layout
+        };
+        assert_eq!(edge.rects(), vec![(1, (0, 0, 800, 600))]);
+    }
+
+    #[test]
+    fn flicker_and_difference_cover_the_surface() {
+        let flicker = Layout {
+            flicker: true,
+            ..layout(4)
+        };
+        assert_eq!(flicker.rects(), vec![(1, (0, 0, 800, 600))]);
+        assert_eq!(flicker.pane_at(Vec2::new(10.0, 10.0)), 1);
+
+        let difference = Layout {
+            comparison: Some(Comparison::Difference),
+            ..layout(2)
+        };
+        assert_eq!(difference.rects(), vec![(0, (0, 0, 800, 600))]);
+        // nothing to compare with
+        assert!(!Layout {
+            flicker: true,
+            ..layout(1)
+        }
+        .overlaid());
+    }
+}
@@ -0,0 +1,351 @@
+use ab_glyph::{Font, FontRef, ScaleFont};
+use bytemuck::{Pod, Zeroable};
+use wgpu::{include_wgsl, util::DeviceExt};
+use winit::dpi::PhysicalSize;
+
+const FONT_BYTES: &[u8] = include_bytes!("../../../assets/DejaVuSansMono.ttf");
+const FONT_SIZE: f32 = 15.0;
+/// Printable ASCII and Latin-1, anything else is drawn as `?`.
+const CHARSETS: [std::ops::RangeInclusive<char>; 2] = [' '..='~', '\u{a0}'..='\u{ff}'];
+const ATLAS_COLUMNS: u32 = 16;
+const TEXT_COLOR: [f32; 4] = [0.95, 0.95, 0.95, 1.0];
+const PANEL_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct TextVertex {
+    position: [f32; 2],
+    tex_coords: [f32; 2],
+    color: [f32; 4],
+}
+
+/// Overlay text drawn on top of the image, laid out in physical pixels from the top-left corner.
+///
+/// The font is monospaced, so every glyph occupies one cell of the atlas and of the screen.
+pub struct TextLayer {
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    screen_buffer: wgpu::Buffer,
+    vertex_buffer: wgpu::Buffer,
+    vertex_capacity: usize,
+    vertices: Vec<TextVertex>,
+    num_vertices: u32,
+    cell: [f32; 2],
+    atlas_size: [f32; 2],
+}
+
+impl TextVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 3] =
+        wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Float32x4];
+
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+impl TextLayer {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        scale_factor: f32,
+    ) -> Self {
+        let font = FontRef::try_from_slice(FONT_BYTES).unwrap();
+        let font = font.as_scaled(FONT_SIZE * scale_factor);
+        let cell = [
+            font.h_advance(font.glyph_id('M')).ceil(),
+            font.height().ceil(),
+        ];
+        let (cell_width, cell_height) = (cell[0] as u32, cell[1] as u32);
+
+        // the last cell stays fully opaque and backs the panels
+        let glyphs = CHARSETS.iter().flat_map(|r| r.clone()).count() as u32;
+        let rows = (glyphs + 1).div_ceil(ATLAS_COLUMNS);
+        let atlas_width = ATLAS_COLUMNS * cell_width;
+        let atlas_height = rows * cell_height;
+        let mut atlas = vec![0u8; (atlas_width * atlas_height) as usize];
+        let chars = CHARSETS.iter().flat_map(|r| r.clone());
+        for (i, c) in chars.enumerate() {
+            let origin_x = (i as u32 % ATLAS_COLUMNS) * cell_width;
+            let origin_y = (i as u32 / ATLAS_COLUMNS) * cell_height;
+            let glyph = font
+                .glyph_id(c)
+                .with_scale_and_position(font.scale(), ab_glyph::point(0.0, font.ascent()));
+            if let Some(outlined) = font.outline_glyph(glyph) {
+                let bounds = outlined.px_bounds();
+                outlined.draw(|x, y, coverage| {
+                    let x = x as i32 + bounds.min.x as i32;
+                    let y = y as i32 + bounds.min.y as i32;
+                    if (0..cell_width as i32).contains(&x) && (0..cell_height as i32).contains(&y) {
+                        let index = (origin_y + y as u32) * atlas_width + origin_x + x as u32;
+                        atlas[index as usize] = (coverage * 255.0) as u8;
+                    }
+                });
+            }
+        }
+        let white = glyphs;
+        for y in 0..cell_height {
+            let row = ((white / ATLAS_COLUMNS) * cell_height + y) * atlas_width;
+            let start = (row + (white % ATLAS_COLUMNS) * cell_width) as usize;
+            atlas[start..start + cell_width as usize].fill(255);
+        }
+
+        let atlas_extent = wgpu::Extent3d {
+            width: atlas_width,
+            height: atlas_height,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: Some("yume glyph atlas"),
+                size: atlas_extent,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::R8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            },
+            &atlas,
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        let screen_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("yume text screen buffer"),
+            size: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("yume text bind group layout"),
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: screen_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+            label: Some("yume text bind group"),
+        });
+
+        let shader = device.create_shader_module(&include_wgsl!("../../../shaders/text.wgsl"));
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("yume text pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("yume text pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[TextVertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                }],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let vertex_capacity = 1024;
+        let vertex_buffer = Self::create_vertex_buffer(device, vertex_capacity);
+
+        Self {
+            pipeline,
+            bind_group,
+            screen_buffer,
+            vertex_buffer,
+            vertex_capacity,
+            vertices: Vec::new(),
+            num_vertices: 0,
+            cell,
+            atlas_size: [atlas_width as f32, atlas_height as f32],
+        }
+    }
+
+    fn create_vertex_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("yume text vertex buffer"),
+            size: (capacity * std::mem::size_of::<TextVertex>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Size of one character cell in pixels.
+    pub fn cell(&self) -> [f32; 2] {
+        self.cell
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+    }
+
+    /// Size of a panel holding `lines`, including its padding.
+    pub fn measure<S: AsRef<str>>(&self, lines: &[S]) -> [f32; 2] {
+        let columns = lines
+            .iter()
+            .map(|line| line.as_ref().chars().count())
+            .max()
+            .unwrap_or(0);
+        [
+            (columns as f32 + 1.0) * self.cell[0],
+            (lines.len() as f32 + 1.0) * self.cell[1],
+        ]
+    }
+
+    /// Adds a translucent panel with `lines` at `origin`, its top-left corner.
+    pub fn panel<S: AsRef<str>>(&mut self, origin: [f32; 2], lines: &[S]) {
+        if lines.is_empty() {
+            return;
+        }
+        let size = self.measure(lines);
+        // sample the middle of the opaque cell so that stretching it never bleeds into a glyph
+        let [u, v, w, h] = self.cell_uv(CHARSETS.iter().flat_map(|r| r.clone()).count());
+        let center = [u + w / 2.0, v + h / 2.0, 0.0, 0.0];
+        self.quad(origin, size, center, PANEL_COLOR);
+
+        let padding = [self.cell[0] / 2.0, self.cell[1] / 2.0];
+        for (row, line) in lines.iter().enumerate() {
+            for (column, c) in line.as_ref().chars().enumerate() {
+                if c == ' ' {
+                    continue;
+                }
+                let position = [
+                    origin[0] + padding[0] + column as f32 * self.cell[0],
+                    origin[1] + padding[1] + row as f32 * self.cell[1],
+                ];
+                self.quad(
+                    position,
+                    self.cell,
+                    self.cell_uv(glyph_index(c)),
+                    TEXT_COLOR,
+                );
+            }
+        }
+    }
+
+    /// Texture coordinates of an atlas cell as `[u, v, width, height]`.
+    fn cell_uv(&self, cell: usize) -> [f32; 4] {
+        let column = (cell as u32 % ATLAS_COLUMNS) as f32;
+        let row = (cell as u32 / ATLAS_COLUMNS) as f32;
+        let size = [
+            self.cell[0] / self.atlas_size[0],
+            self.cell[1] / self.atlas_size[1],
+        ];
+        [column * size[0], row * size[1], size[0], size[1]]
+    }
+
+    fn quad(&mut self, origin: [f32; 2], size: [f32; 2], uv: [f32; 4], color: [f32; 4]) {
+        let [u0, v0, w, h] = uv;
+        let (u1, v1) = (u0 + w, v0 + h);
+        let (x0, y0) = (origin[0], origin[1]);
+        let (x1, y1) = (x0 + size[0], y0 + size[1]);
+        let vertex = |x, y, u, v| TextVertex {
+            position: [x, y],
+            tex_coords: [u, v],
+            color,
+        };
+        self.vertices.extend([
+            vertex(x0, y0, u0, v0),
+            vertex(x0, y1, u0, v1),
+            vertex(x1, y1, u1, v1),
+            vertex(x0, y0, u0, v0),
+            vertex(x1, y1, u1, v1),
+            vertex(x1, y0, u1, v0),
+        ]);
+    }
+
+    /// Uploads the queued text, to be called once per frame before `draw`.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, size: PhysicalSize<u32>) {
+        if self.vertices.len() > self.vertex_capacity {
+            self.vertex_capacity = self.vertices.len().next_power_of_two();
+            self.vertex_buffer = Self::create_vertex_buffer(device, self.vertex_capacity);
+        }
+        queue.write_buffer(
+            &self.screen_buffer,
+            0,
+            bytemuck::cast_slice(&[size.width as f32, size.height as f32, 0.0, 0.0]),
+        );
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&self.vertices));
+        self.num_vertices = self.vertices.len() as u32;
+    }
+
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        if self.num_vertices == 0 {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(0..self.num_vertices, 0..1);
+    }
+}
+
+fn glyph_index(c: char) -> usize {
+    let mut index = 0;
+    for range in CHARSETS.iter() {
+        if range.contains(&c) {
+            return index + (c as usize - *range.start() as usize);
+        }
+        index += range.clone().count();
+    }
+    glyph_index('?')
+}
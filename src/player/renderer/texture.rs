@@ -1,19 +1,236 @@
-use image::Rgba;
+use image::{imageops, DynamicImage, ImageBuffer, Rgba};
+use std::{borrow::Cow, sync::OnceLock};
 
-pub type Rgba8Image = image::ImageBuffer<Rgba<u8>, Vec<u8>>;
+pub type Rgba8Image = ImageBuffer<Rgba<u8>, Vec<u8>>;
+pub type Rgba16Image = ImageBuffer<Rgba<u16>, Vec<u16>>;
+pub type Rgba32FImage = ImageBuffer<Rgba<f32>, Vec<f32>>;
+
+/// Decoded pixels, kept in the precision they were stored with.
+#[derive(Debug, Clone)]
+pub enum Pixels {
+    /// sRGB encoded.
+    Rgba8(Rgba8Image),
+    /// sRGB encoded, such as 16-bit PNG and TIFF.
+    Rgba16(Rgba16Image),
+    /// Linear light, such as OpenEXR and Radiance HDR.
+    Rgba32F(Rgba32FImage),
+}
+
+/// One pixel of `Pixels`, as stored.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Texel {
+    Rgba8([u8; 4]),
+    Rgba16([u16; 4]),
+    Rgba32F([f32; 4]),
+}
 
 pub struct Texture {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
-    pub sampler: wgpu::Sampler,
     pub size: wgpu::Extent3d,
+    pub format: wgpu::TextureFormat,
+    /// Number of mip levels, down to a single pixel.
+    pub mip_level_count: u32,
+}
+
+impl Pixels {
+    pub fn dimensions(&self) -> (u32, u32) {
+        match self {
+            Self::Rgba8(img) => img.dimensions(),
+            Self::Rgba16(img) => img.dimensions(),
+            Self::Rgba32F(img) => img.dimensions(),
+        }
+    }
+
+    pub fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> Self {
+        match self {
+            Self::Rgba8(img) => {
+                Self::Rgba8(imageops::crop_imm(img, x, y, width, height).to_image())
+            }
+            Self::Rgba16(img) => {
+                Self::Rgba16(imageops::crop_imm(img, x, y, width, height).to_image())
+            }
+            Self::Rgba32F(img) => {
+                Self::Rgba32F(imageops::crop_imm(img, x, y, width, height).to_image())
+            }
+        }
+    }
+
+    pub fn texel(&self, x: u32, y: u32) -> Option<Texel> {
+        let (width, height) = self.dimensions();
+        if x >= width || y >= height {
+            return None;
+        }
+        Some(match self {
+            Self::Rgba8(img) => Texel::Rgba8(img.get_pixel(x, y).0),
+            Self::Rgba16(img) => Texel::Rgba16(img.get_pixel(x, y).0),
+            Self::Rgba32F(img) => Texel::Rgba32F(img.get_pixel(x, y).0),
+        })
+    }
+
+    /// An sRGB copy that fits in a `size` by `size` square, never scaled up.
+    pub fn thumbnail(&self, size: u32) -> Rgba8Image {
+        let (width, height) = self.dimensions();
+        let scale = (size as f32 / width.max(height).max(1) as f32).min(1.0);
+        let width = ((width as f32 * scale).round() as u32).max(1);
+        let height = ((height as f32 * scale).round() as u32).max(1);
+        match self {
+            Self::Rgba8(img) => imageops::thumbnail(img, width, height),
+            Self::Rgba16(img) => {
+                DynamicImage::ImageRgba16(imageops::thumbnail(img, width, height)).to_rgba8()
+            }
+            Self::Rgba32F(img) => {
+                let small = imageops::thumbnail(img, width, height);
+                let encode = |v: f32| (linear_to_srgb(v.clamp(0.0, 1.0)) * 255.0).round() as u8;
+                Rgba8Image::from_fn(width, height, |x, y| {
+                    let [r, g, b, a] = small.get_pixel(x, y).0;
+                    Rgba([
+                        encode(r),
+                        encode(g),
+                        encode(b),
+                        (a.clamp(0.0, 1.0) * 255.0) as u8,
+                    ])
+                })
+            }
+        }
+    }
+
+    /// The texture format these pixels are uploaded as, given the format used for
+    /// anything with more than 8 bits per channel.
+    pub fn texture_format(&self, float_format: wgpu::TextureFormat) -> wgpu::TextureFormat {
+        match self {
+            Self::Rgba8(_) => wgpu::TextureFormat::Rgba8UnormSrgb,
+            _ => float_format,
+        }
+    }
+
+    /// Texel data in `format`. Float formats hold linear values.
+    fn texels(&self, format: wgpu::TextureFormat) -> Cow<'_, [u8]> {
+        let linear: Cow<'_, [f32]> = match self {
+            Self::Rgba8(img) => return Cow::Borrowed(img.as_raw()),
+            Self::Rgba16(img) => {
+                let lut = linear_lut();
+                img.pixels()
+                    .flat_map(|p| {
+                        let [r, g, b, a] = p.0;
+                        [lut[r as usize], lut[g as usize], lut[b as usize]]
+                            .into_iter()
+                            .chain([a as f32 / u16::MAX as f32])
+                    })
+                    .collect()
+            }
+            Self::Rgba32F(img) => Cow::Borrowed(img.as_raw()),
+        };
+        match format {
+            wgpu::TextureFormat::Rgba16Float => {
+                let half: Vec<u16> = linear
+                    .iter()
+                    .map(|&v| half::f16::from_f32(v).to_bits())
+                    .collect();
+                Cow::Owned(bytemuck::cast_slice(&half).to_vec())
+            }
+            _ => match linear {
+                Cow::Borrowed(linear) => Cow::Borrowed(bytemuck::cast_slice(linear)),
+                Cow::Owned(linear) => Cow::Owned(bytemuck::cast_slice(&linear).to_vec()),
+            },
+        }
+    }
+}
+
+impl Texel {
+    /// Integer channels scaled to 0..1, floats as stored.
+    pub fn to_f32(self) -> [f32; 4] {
+        match self {
+            Self::Rgba8(c) => c.map(|v| v as f32 / u8::MAX as f32),
+            Self::Rgba16(c) => c.map(|v| v as f32 / u16::MAX as f32),
+            Self::Rgba32F(c) => c,
+        }
+    }
+
+    /// `#rrggbbaa` with two hex digits per byte stored, floats clamped to 8 bits.
+    pub fn hex(self) -> String {
+        let digits: Vec<String> = match self {
+            Self::Rgba8(c) => c.iter().map(|v| format!("{:02x}", v)).collect(),
+            Self::Rgba16(c) => c.iter().map(|v| format!("{:04x}", v)).collect(),
+            Self::Rgba32F(c) => c
+                .iter()
+                .map(|v| format!("{:02x}", (v.clamp(0.0, 1.0) * 255.0).round() as u8))
+                .collect(),
+        };
+        format!("#{}", digits.concat())
+    }
+
+    /// Channels as stored, separated by spaces.
+    pub fn raw(self) -> String {
+        let channels: Vec<String> = match self {
+            Self::Rgba8(c) => c.iter().map(ToString::to_string).collect(),
+            Self::Rgba16(c) => c.iter().map(ToString::to_string).collect(),
+            Self::Rgba32F(c) => c.iter().map(|v| format!("{:.4}", v)).collect(),
+        };
+        channels.join(" ")
+    }
+}
+
+impl Default for Pixels {
+    fn default() -> Self {
+        Self::Rgba8(Rgba8Image::default())
+    }
+}
+
+impl From<Rgba8Image> for Pixels {
+    fn from(img: Rgba8Image) -> Self {
+        Self::Rgba8(img)
+    }
+}
+
+impl From<DynamicImage> for Pixels {
+    fn from(img: DynamicImage) -> Self {
+        match img {
+            DynamicImage::ImageRgba8(img) => Self::Rgba8(img),
+            DynamicImage::ImageLuma16(_)
+            | DynamicImage::ImageLumaA16(_)
+            | DynamicImage::ImageRgb16(_)
+            | DynamicImage::ImageRgba16(_) => Self::Rgba16(img.to_rgba16()),
+            DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_) => {
+                Self::Rgba32F(img.to_rgba32f())
+            }
+            _ => Self::Rgba8(img.to_rgba8()),
+        }
+    }
+}
+
+/// Linear values of all 16-bit sRGB values.
+fn linear_lut() -> &'static [f32] {
+    static LUT: OnceLock<Vec<f32>> = OnceLock::new();
+    LUT.get_or_init(|| {
+        (0..=u16::MAX)
+            .map(|v| srgb_to_linear(v as f32 / u16::MAX as f32))
+            .collect()
+    })
+}
+
+pub(crate) fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(v: f32) -> f32 {
+    if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
 }
 
 impl Texture {
     pub fn from_image(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
-        img: &Rgba8Image,
+        img: &Pixels,
+        float_format: wgpu::TextureFormat,
         label: Option<&str>,
     ) -> Self {
         let dimensions = img.dimensions();
@@ -23,48 +240,60 @@ impl Texture {
             height: dimensions.1,
             depth_or_array_layers: 1,
         };
+        let format = img.texture_format(float_format);
+        let mip_level_count = u32::BITS - dimensions.0.max(dimensions.1).leading_zeros();
         let texture = device.create_texture(&wgpu::TextureDescriptor {
             label,
             size,
-            mip_level_count: 1,
+            mip_level_count,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            format,
+            // lower mip levels are rendered from the first one
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::RENDER_ATTACHMENT,
         });
 
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let texture = Self {
+            texture,
+            view,
+            size,
+            format,
+            mip_level_count,
+        };
+        texture.write(queue, img);
+        texture
+    }
+
+    /// Whether `img` can be written into this texture.
+    pub fn fits(&self, img: &Pixels) -> bool {
+        let float = self.format != wgpu::TextureFormat::Rgba8UnormSrgb;
+        img.dimensions() == (self.size.width, self.size.height)
+            && float != matches!(img, Pixels::Rgba8(_))
+    }
+
+    /// Overwrites the first mip level in place with an image of the same dimensions and format.
+    pub fn write(&self, queue: &wgpu::Queue, img: &Pixels) {
+        assert!(self.fits(img));
+
+        let bytes_per_pixel = self.format.describe().block_size as u32;
         queue.write_texture(
             wgpu::ImageCopyTexture {
                 aspect: wgpu::TextureAspect::All,
-                texture: &texture,
+                texture: &self.texture,
                 mip_level: 0,
                 origin: wgpu::Origin3d::ZERO,
             },
-            img,
+            &img.texels(self.format),
             wgpu::ImageDataLayout {
                 offset: 0,
-                bytes_per_row: std::num::NonZeroU32::new(4 * dimensions.0),
-                rows_per_image: std::num::NonZeroU32::new(dimensions.1),
+                bytes_per_row: std::num::NonZeroU32::new(bytes_per_pixel * self.size.width),
+                rows_per_image: std::num::NonZeroU32::new(self.size.height),
             },
-            size,
+            self.size,
         );
-
-        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToBorder,
-            address_mode_v: wgpu::AddressMode::ClampToBorder,
-            address_mode_w: wgpu::AddressMode::ClampToBorder,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Linear,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
-        });
-
-        Self {
-            texture,
-            view,
-            sampler,
-            size,
-        }
     }
 }
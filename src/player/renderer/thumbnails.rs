@@ -0,0 +1,458 @@
+use super::texture::Rgba8Image;
+use bytemuck::{Pod, Zeroable};
+use std::collections::HashMap;
+use wgpu::include_wgsl;
+use winit::dpi::PhysicalSize;
+
+/// Width and height of each atlas texture.
+const ATLAS_SIZE: u32 = 2048;
+/// Atlases kept at most, about 16 MB each. The least recently drawn thumbnails make room
+/// for new ones beyond that.
+const MAX_ATLASES: u32 = 4;
+const WHITE: [f32; 4] = [1.0; 4];
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct ThumbnailVertex {
+    position: [f32; 2],
+    tex_coords: [f32; 2],
+    color: [f32; 4],
+}
+
+/// A texture holding many thumbnails in a grid of cells.
+struct Atlas {
+    texture: wgpu::Texture,
+    bind_group: wgpu::BindGroup,
+    /// Quads queued for this frame.
+    vertices: Vec<ThumbnailVertex>,
+}
+
+/// Where a thumbnail is stored.
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    atlas: usize,
+    cell: u32,
+    size: (u32, u32),
+    /// Frame the thumbnail was last drawn in.
+    used: u64,
+}
+
+/// Thumbnails packed into texture atlases and drawn as quads, in physical pixels from the
+/// top-left corner.
+///
+/// The last cell of every atlas is opaque white and backs plain rectangles.
+pub struct ThumbnailLayer {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    screen_buffer: wgpu::Buffer,
+    vertex_buffer: wgpu::Buffer,
+    vertex_capacity: usize,
+    /// Plain rectangles queued for this frame, drawn from the first atlas.
+    rects: Vec<ThumbnailVertex>,
+    /// Atlas and vertex range of each draw call.
+    draws: Vec<(usize, std::ops::Range<u32>)>,
+    atlases: Vec<Atlas>,
+    /// Stored thumbnails by playlist index.
+    slots: HashMap<usize, Slot>,
+    /// Cell of the next thumbnail, counted across all atlases, until they are all taken.
+    next: u32,
+    /// Frames queued so far.
+    frame: u64,
+    /// Thumbnail size plus a pixel of padding on each side.
+    cell: u32,
+    columns: u32,
+}
+
+impl ThumbnailVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 3] =
+        wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Float32x4];
+
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+impl ThumbnailLayer {
+    /// A layer for thumbnails no larger than `size` pixels on either side.
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        size: u32,
+    ) -> Self {
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let screen_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("yume thumbnail screen buffer"),
+            size: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("yume thumbnail bind group layout"),
+        });
+
+        let shader =
+            device.create_shader_module(&include_wgsl!("../../../shaders/thumbnails.wgsl"));
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("yume thumbnail pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("yume thumbnail pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[ThumbnailVertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                }],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let vertex_capacity = 1024;
+        let vertex_buffer = Self::create_vertex_buffer(device, vertex_capacity);
+        let cell = size + 2;
+        let mut layer = Self {
+            pipeline,
+            bind_group_layout,
+            sampler,
+            screen_buffer,
+            vertex_buffer,
+            vertex_capacity,
+            rects: Vec::new(),
+            draws: Vec::new(),
+            atlases: Vec::new(),
+            slots: HashMap::new(),
+            next: 0,
+            frame: 0,
+            cell,
+            columns: ATLAS_SIZE / cell,
+        };
+        // rectangles are drawn from the first atlas, even without any thumbnails
+        layer.add_atlas(device, queue);
+        layer
+    }
+
+    fn create_vertex_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("yume thumbnail vertex buffer"),
+            size: (capacity * std::mem::size_of::<ThumbnailVertex>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Thumbnails each atlas holds, leaving out the white cell.
+    fn cells_per_atlas(&self) -> u32 {
+        self.columns * self.columns - 1
+    }
+
+    fn add_atlas(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("yume thumbnail atlas"),
+            size: wgpu::Extent3d {
+                width: ATLAS_SIZE,
+                height: ATLAS_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        });
+        let white = Rgba8Image::from_pixel(self.cell, self.cell, image::Rgba([255; 4]));
+        write(
+            queue,
+            &texture,
+            self.cell_origin(self.cells_per_atlas()),
+            &white,
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.screen_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+            label: Some("yume thumbnail bind group"),
+        });
+        self.atlases.push(Atlas {
+            texture,
+            bind_group,
+            vertices: Vec::new(),
+        });
+    }
+
+    fn cell_origin(&self, cell: u32) -> (u32, u32) {
+        (
+            cell % self.columns * self.cell,
+            cell / self.columns * self.cell,
+        )
+    }
+
+    /// Stores the thumbnail of playlist item `index`, returning the item whose thumbnail it
+    /// replaced once the atlases are full.
+    pub fn insert(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        index: usize,
+        img: &Rgba8Image,
+    ) -> Option<usize> {
+        let mut evicted = None;
+        let (atlas, cell) = if self.next < self.cells_per_atlas() * MAX_ATLASES {
+            let atlas = (self.next / self.cells_per_atlas()) as usize;
+            if atlas == self.atlases.len() {
+                self.add_atlas(device, queue);
+            }
+            self.next += 1;
+            (atlas, (self.next - 1) % self.cells_per_atlas())
+        } else {
+            let (&old, _) = self.slots.iter().min_by_key(|(_, slot)| slot.used)?;
+            let slot = self.slots.remove(&old)?;
+            evicted = Some(old);
+            (slot.atlas, slot.cell)
+        };
+        let (x, y) = self.cell_origin(cell);
+        if evicted.is_some() {
+            // the replaced thumbnail may have been larger
+            let blank = Rgba8Image::new(self.cell - 2, self.cell - 2);
+            write(queue, &self.atlases[atlas].texture, (x + 1, y + 1), &blank);
+        }
+        write(queue, &self.atlases[atlas].texture, (x + 1, y + 1), img);
+        self.slots.insert(
+            index,
+            Slot {
+                atlas,
+                cell,
+                size: img.dimensions(),
+                used: self.frame,
+            },
+        );
+        evicted
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        self.slots.contains_key(&index)
+    }
+
+    /// Forgets every thumbnail, keeping the textures for the next ones.
+    pub fn reset(&mut self) {
+        self.slots.clear();
+        self.next = 0;
+    }
+
+    /// Removes the quads queued for the previous frame.
+    pub fn clear(&mut self) {
+        self.frame += 1;
+        self.rects.clear();
+        for atlas in &mut self.atlases {
+            atlas.vertices.clear();
+        }
+    }
+
+    /// Queues the thumbnail of item `index` centered in the square of `size` pixels at
+    /// `origin`, returning whether there is one.
+    pub fn thumbnail(&mut self, index: usize, origin: [f32; 2], size: f32) -> bool {
+        let slot = match self.slots.get_mut(&index) {
+            Some(slot) => {
+                slot.used = self.frame;
+                *slot
+            }
+            None => return false,
+        };
+        let (width, height) = (slot.size.0 as f32, slot.size.1 as f32);
+        // only shrunk, thumbnails are drawn at their own size when they fit
+        let scale = (size / width.max(height)).min(1.0);
+        let shown = [width * scale, height * scale];
+        let position = [
+            (origin[0] + (size - shown[0]) / 2.0).round(),
+            (origin[1] + (size - shown[1]) / 2.0).round(),
+        ];
+        let (x, y) = self.cell_origin(slot.cell);
+        let uv = [
+            (x + 1) as f32 / ATLAS_SIZE as f32,
+            (y + 1) as f32 / ATLAS_SIZE as f32,
+            width / ATLAS_SIZE as f32,
+            height / ATLAS_SIZE as f32,
+        ];
+        quad(
+            &mut self.atlases[slot.atlas].vertices,
+            position,
+            shown,
+            uv,
+            WHITE,
+        );
+        true
+    }
+
+    /// Queues a plain rectangle, drawn before every thumbnail.
+    pub fn rect(&mut self, origin: [f32; 2], size: [f32; 2], color: [f32; 4]) {
+        // sample the middle of the white cell so that filtering never reaches a thumbnail
+        let (x, y) = self.cell_origin(self.cells_per_atlas());
+        let center = (
+            (x + self.cell / 2) as f32 / ATLAS_SIZE as f32,
+            (y + self.cell / 2) as f32 / ATLAS_SIZE as f32,
+        );
+        quad(
+            &mut self.rects,
+            origin,
+            size,
+            [center.0, center.1, 0.0, 0.0],
+            color,
+        );
+    }
+
+    /// Uploads the queued quads, to be called once per frame before `draw`.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, size: PhysicalSize<u32>) {
+        let count = self.rects.len() + self.atlases.iter().map(|a| a.vertices.len()).sum::<usize>();
+        if count > self.vertex_capacity {
+            self.vertex_capacity = count.next_power_of_two();
+            self.vertex_buffer = Self::create_vertex_buffer(device, self.vertex_capacity);
+        }
+        queue.write_buffer(
+            &self.screen_buffer,
+            0,
+            bytemuck::cast_slice(&[size.width as f32, size.height as f32, 0.0, 0.0]),
+        );
+        self.draws.clear();
+        let mut vertices = Vec::with_capacity(count);
+        vertices.extend_from_slice(&self.rects);
+        if !self.rects.is_empty() {
+            self.draws.push((0, 0..vertices.len() as u32));
+        }
+        for (i, atlas) in self.atlases.iter().enumerate() {
+            if !atlas.vertices.is_empty() {
+                let start = vertices.len() as u32;
+                vertices.extend_from_slice(&atlas.vertices);
+                self.draws.push((i, start..vertices.len() as u32));
+            }
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertices));
+    }
+
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        if self.draws.is_empty() {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        for (atlas, range) in &self.draws {
+            render_pass.set_bind_group(0, &self.atlases[*atlas].bind_group, &[]);
+            render_pass.draw(range.clone(), 0..1);
+        }
+    }
+}
+
+fn write(queue: &wgpu::Queue, texture: &wgpu::Texture, origin: (u32, u32), img: &Rgba8Image) {
+    let (width, height) = img.dimensions();
+    queue.write_texture(
+        wgpu::ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d {
+                x: origin.0,
+                y: origin.1,
+                z: 0,
+            },
+            aspect: wgpu::TextureAspect::All,
+        },
+        img.as_raw(),
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: std::num::NonZeroU32::new(4 * width),
+            rows_per_image: std::num::NonZeroU32::new(height),
+        },
+        wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+}
+
+fn quad(
+    vertices: &mut Vec<ThumbnailVertex>,
+    origin: [f32; 2],
+    size: [f32; 2],
+    uv: [f32; 4],
+    color: [f32; 4],
+) {
+    let [u0, v0, w, h] = uv;
+    let (u1, v1) = (u0 + w, v0 + h);
+    let (x0, y0) = (origin[0], origin[1]);
+    let (x1, y1) = (x0 + size[0], y0 + size[1]);
+    let vertex = |x, y, u, v| ThumbnailVertex {
+        position: [x, y],
+        tex_coords: [u, v],
+        color,
+    };
+    vertices.extend([
+        vertex(x0, y0, u0, v0),
+        vertex(x0, y1, u0, v1),
+        vertex(x1, y1, u1, v1),
+        vertex(x0, y0, u0, v0),
+        vertex(x1, y1, u1, v1),
+        vertex(x1, y0, u1, v0),
+    ]);
+}
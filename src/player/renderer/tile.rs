@@ -0,0 +1,188 @@
+use super::{
+    mipmap::MipGenerator,
+    texture::{Pixels, Texture},
+};
+use std::{borrow::Cow, ops::Range};
+
+/// Pixels each tile shares with its neighbours, so filters reaching past the edge of a tile
+/// read the image rather than clamped texels: enough for Lanczos on the first levels of the
+/// mipmaps and for linear filtering on a few more.
+const OVERLAP: u32 = 16;
+
+/// Part of an image small enough to fit in one texture.
+///
+/// Images within the texture size limit are made of a single tile.
+pub struct Tile {
+    pub texture: Texture,
+    pub bind_group: wgpu::BindGroup,
+    /// Position of the top left corner of the part drawn by this tile in the image.
+    pub origin: (u32, u32),
+    /// Size of the part drawn by this tile.
+    pub size: (u32, u32),
+    /// Position of `origin` in the texture, which also holds some of the neighbouring tiles.
+    pub offset: (u32, u32),
+    /// Whether any of the tile is on screen, updated with the vertex buffer.
+    pub visible: bool,
+}
+
+/// A row or column of a tile: the pixels drawn, and the pixels in its texture.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Span {
+    drawn: Range<u32>,
+    stored: Range<u32>,
+}
+
+/// Splits `len` pixels into spans of at most `max` stored pixels, overlapping by `OVERLAP`.
+fn spans(len: u32, max: u32) -> Vec<Span> {
+    if len <= max {
+        return vec![Span {
+            drawn: 0..len,
+            stored: 0..len,
+        }];
+    }
+    let step = max - 2 * OVERLAP;
+    (0..len)
+        .step_by(step as usize)
+        .map(|start| {
+            let end = (start + step).min(len);
+            Span {
+                drawn: start..end,
+                stored: start.saturating_sub(OVERLAP)..(end + OVERLAP).min(len),
+            }
+        })
+        .collect()
+}
+
+impl Tile {
+    /// Splits `img` into tiles no larger than the device allows.
+    pub fn split(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        mipmaps: &MipGenerator,
+        img: &Pixels,
+        float_format: wgpu::TextureFormat,
+    ) -> Vec<Self> {
+        let max = device.limits().max_texture_dimension_2d;
+        let (width, height) = img.dimensions();
+        let mut tiles = Vec::new();
+        for row in spans(height, max) {
+            for column in spans(width, max) {
+                let stored = (column.stored.start, row.stored.start);
+                let texture = Texture::from_image(
+                    device,
+                    queue,
+                    &crop(
+                        img,
+                        stored,
+                        (column.stored.len() as u32, row.stored.len() as u32),
+                    ),
+                    float_format,
+                    Some("yume texture"),
+                );
+                mipmaps.generate(device, queue, &texture);
+                tiles.push(Self {
+                    bind_group: bind_group(device, layout, &texture),
+                    texture,
+                    origin: (column.drawn.start, row.drawn.start),
+                    size: (column.drawn.len() as u32, row.drawn.len() as u32),
+                    offset: (
+                        column.drawn.start - column.stored.start,
+                        row.drawn.start - row.stored.start,
+                    ),
+                    visible: true,
+                });
+            }
+        }
+        tiles
+    }
+
+    /// Overwrites the tile with its part of an image of the size it was split from.
+    pub fn write(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        mipmaps: &MipGenerator,
+        img: &Pixels,
+    ) {
+        let stored = (self.origin.0 - self.offset.0, self.origin.1 - self.offset.1);
+        let size = (self.texture.size.width, self.texture.size.height);
+        self.texture.write(queue, &crop(img, stored, size));
+        mipmaps.generate(device, queue, &self.texture);
+    }
+
+    /// Texture coordinates of the top left and bottom right corners of the drawn part.
+    pub fn tex_coords(&self) -> ([f32; 2], [f32; 2]) {
+        let (width, height) = (
+            self.texture.size.width as f32,
+            self.texture.size.height as f32,
+        );
+        let (x, y) = (self.offset.0 as f32, self.offset.1 as f32);
+        (
+            [x / width, y / height],
+            [
+                (x + self.size.0 as f32) / width,
+                (y + self.size.1 as f32) / height,
+            ],
+        )
+    }
+}
+
+/// The part of `img` at `origin`, without copying images that are a single tile.
+fn crop(img: &Pixels, origin: (u32, u32), size: (u32, u32)) -> Cow<'_, Pixels> {
+    if origin == (0, 0) && size == img.dimensions() {
+        Cow::Borrowed(img)
+    } else {
+        Cow::Owned(img.crop(origin.0, origin.1, size.0, size.1))
+    }
+}
+
+pub fn bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    texture: &Texture,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::TextureView(&texture.view),
+        }],
+        label: Some("yume diffuse bind group"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_images_are_one_span() {
+        assert_eq!(
+            spans(100, 100),
+            vec![Span {
+                drawn: 0..100,
+                stored: 0..100
+            }]
+        );
+    }
+
+    #[test]
+    fn spans_cover_the_image_with_overlap() {
+        let (len, max) = (1000, 256);
+        let spans = spans(len, max);
+        assert_eq!(spans.first().unwrap().drawn.start, 0);
+        assert_eq!(spans.last().unwrap().drawn.end, len);
+        for pair in spans.windows(2) {
+            assert_eq!(pair[0].drawn.end, pair[1].drawn.start);
+        }
+        for span in &spans {
+            assert!(span.stored.len() as u32 <= max);
+            // neighbours are stored around every edge between tiles
+            let before = span.drawn.start - span.stored.start;
+            let after = span.stored.end - span.drawn.end;
+            assert!(span.drawn.start == 0 || before == OVERLAP);
+            assert!(span.drawn.end == len || after == OVERLAP);
+        }
+    }
+}
@@ -0,0 +1,365 @@
+use std::f32::consts::TAU;
+use ultraviolet::{Mat2, Mat4, Vec2, Vec4};
+
+/// How the image is placed on the surface.
+///
+/// Three coordinate spaces are involved, all in pixels with y pointing down:
+/// - screen: physical surface pixels from the top left corner, like cursor positions
+/// - view: screen pixels relative to the center of the surface
+/// - image: image pixels from the top left corner of the image
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct View {
+    /// Image point shown at the center of the surface.
+    pub center: Vec2,
+    /// Screen pixels per image pixel.
+    pub scale: f32,
+    /// Clockwise rotation in radians.
+    pub rotation: f32,
+    /// Mirror the image left to right, before rotating it.
+    pub flip_horizontal: bool,
+    /// Mirror the image top to bottom, before rotating it.
+    pub flip_vertical: bool,
+}
+
+impl Default for View {
+    fn default() -> Self {
+        Self {
+            center: Vec2::zero(),
+            scale: 1.0,
+            rotation: 0.0,
+            flip_horizontal: false,
+            flip_vertical: false,
+        }
+    }
+}
+
+impl View {
+    /// Shows the middle of an image of `size` at 100%.
+    pub fn centered(size: Vec2) -> Self {
+        Self {
+            center: size / 2.0,
+            ..Default::default()
+        }
+    }
+
+    /// Image to view, without the translation.
+    fn linear(&self) -> Mat2 {
+        let (sin, cos) = self.rotation.sin_cos();
+        let rotation = Mat2::new(Vec2::new(cos, sin), Vec2::new(-sin, cos));
+        let flip = |flipped| if flipped { -1.0 } else { 1.0 };
+        let flip = Mat2::new(
+            Vec2::new(flip(self.flip_horizontal), 0.0),
+            Vec2::new(0.0, flip(self.flip_vertical)),
+        );
+        rotation * flip * self.scale
+    }
+
+    pub fn image_to_view(&self, p: Vec2) -> Vec2 {
+        self.linear() * (p - self.center)
+    }
+
+    pub fn view_to_image(&self, p: Vec2) -> Vec2 {
+        self.linear().inversed() * p + self.center
+    }
+
+    pub fn screen_to_view(p: Vec2, surface: Vec2) -> Vec2 {
+        p - surface / 2.0
+    }
+
+    pub fn view_to_screen(p: Vec2, surface: Vec2) -> Vec2 {
+        p + surface / 2.0
+    }
+
+    pub fn image_to_screen(&self, p: Vec2, surface: Vec2) -> Vec2 {
+        Self::view_to_screen(self.image_to_view(p), surface)
+    }
+
+    pub fn screen_to_image(&self, p: Vec2, surface: Vec2) -> Vec2 {
+        self.view_to_image(Self::screen_to_view(p, surface))
+    }
+
+    /// Moves the image by `delta` screen pixels.
+    pub fn pan(&mut self, delta: Vec2) {
+        self.center -= self.linear().inversed() * delta;
+    }
+
+    /// Changes the scale while the image point at `anchor`, in view coordinates, stays put.
+    pub fn zoom_at(&mut self, scale: f32, anchor: Vec2) {
+        let fixed = self.view_to_image(anchor);
+        self.scale = scale;
+        self.center += fixed - self.view_to_image(anchor);
+    }
+
+    /// Turns the image clockwise around the center of the surface.
+    pub fn rotate(&mut self, angle: f32) {
+        self.rotation = (self.rotation + angle).rem_euclid(TAU);
+    }
+
+    /// Mirrors the image left to right as it is shown on screen.
+    pub fn mirror_horizontal(&mut self) {
+        // mirroring after a turn equals mirroring before the opposite turn
+        self.rotation = (-self.rotation).rem_euclid(TAU);
+        self.flip_horizontal = !self.flip_horizontal;
+    }
+
+    /// Mirrors the image top to bottom as it is shown on screen.
+    pub fn mirror_vertical(&mut self) {
+        self.rotation = (-self.rotation).rem_euclid(TAU);
+        self.flip_vertical = !self.flip_vertical;
+    }
+
+    /// The same view moved by less than a pixel so image pixel corners land on screen pixel
+    /// corners, which keeps whole-number scales sharp.
+    pub fn pixel_aligned(&self, surface: Vec2) -> Self {
+        let origin = self.image_to_screen(Vec2::zero(), surface);
+        let offset = Vec2::new(origin.x.round(), origin.y.round()) - origin;
+        let mut view = *self;
+        view.pan(offset);
+        view
+    }
+
+    /// Size of the screen-aligned box around an image of `size` at 100%, after rotation.
+    pub fn bounds(&self, size: Vec2) -> Vec2 {
+        let (sin, cos) = self.rotation.sin_cos();
+        let (sin, cos) = (sin.abs(), cos.abs());
+        Vec2::new(size.x * cos + size.y * sin, size.x * sin + size.y * cos)
+    }
+
+    /// Pans an image of `size` back so it covers as much of the surface as it can:
+    /// centered along axes where it is smaller than the surface, edges past the surface
+    /// edges otherwise.
+    pub fn clamp(&mut self, size: Vec2, surface: Vec2) {
+        let center = self.image_to_view(size / 2.0);
+        let half = self.bounds(size) * self.scale / 2.0;
+        let room = (half - surface / 2.0).max_by_component(Vec2::zero());
+        let clamped = center.clamped(-room, room);
+        self.pan(clamped - center);
+    }
+
+    /// Image to clip space, for the vertex shader.
+    pub fn transform(&self, surface: Vec2) -> Mat4 {
+        // clip space spans 2 units across the surface and points up
+        let to_clip = Mat2::new(
+            Vec2::new(2.0 / surface.x, 0.0),
+            Vec2::new(0.0, -2.0 / surface.y),
+        );
+        let m = to_clip * self.linear();
+        let t = -(m * self.center);
+        Mat4::new(
+            Vec4::new(m.cols[0].x, m.cols[0].y, 0.0, 0.0),
+            Vec4::new(m.cols[1].x, m.cols[1].y, 0.0, 0.0),
+            Vec4::new(0.0, 0.0, 1.0, 0.0),
+            Vec4::new(t.x, t.y, 0.0, 1.0),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    const SURFACE: Vec2 = Vec2::new(800.0, 600.0);
+
+    fn assert_close(a: Vec2, b: Vec2) {
+        assert!((a - b).mag() < 1e-3, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn centered_image_fills_the_middle() {
+        let view = View::centered(Vec2::new(400.0, 200.0));
+        assert_close(
+            view.image_to_screen(Vec2::zero(), SURFACE),
+            Vec2::new(200.0, 200.0),
+        );
+        assert_close(
+            view.image_to_screen(Vec2::new(400.0, 200.0), SURFACE),
+            Vec2::new(600.0, 400.0),
+        );
+    }
+
+    #[test]
+    fn scale_is_around_the_center() {
+        let view = View {
+            scale: 2.0,
+            ..View::centered(Vec2::new(100.0, 100.0))
+        };
+        assert_close(view.image_to_view(Vec2::new(50.0, 50.0)), Vec2::zero());
+        assert_close(
+            view.image_to_view(Vec2::new(60.0, 40.0)),
+            Vec2::new(20.0, -20.0),
+        );
+    }
+
+    #[test]
+    fn rotation_is_clockwise() {
+        let view = View {
+            rotation: FRAC_PI_2,
+            ..Default::default()
+        };
+        // right of the center ends up below it
+        assert_close(
+            view.image_to_view(Vec2::new(10.0, 0.0)),
+            Vec2::new(0.0, 10.0),
+        );
+    }
+
+    #[test]
+    fn flips_happen_before_rotation() {
+        let view = View {
+            rotation: FRAC_PI_2,
+            flip_horizontal: true,
+            ..Default::default()
+        };
+        assert_close(
+            view.image_to_view(Vec2::new(10.0, 0.0)),
+            Vec2::new(0.0, -10.0),
+        );
+        let view = View {
+            flip_vertical: true,
+            ..Default::default()
+        };
+        assert_close(
+            view.image_to_view(Vec2::new(3.0, 4.0)),
+            Vec2::new(3.0, -4.0),
+        );
+    }
+
+    #[test]
+    fn mirrors_are_on_screen() {
+        let mut view = View::default();
+        view.rotate(FRAC_PI_2);
+        let p = Vec2::new(10.0, 3.0);
+        let shown = view.image_to_view(p);
+        view.mirror_horizontal();
+        assert_close(view.image_to_view(p), Vec2::new(-shown.x, shown.y));
+        view.mirror_vertical();
+        assert_close(view.image_to_view(p), -shown);
+    }
+
+    #[test]
+    fn rotated_bounds() {
+        let mut view = View::default();
+        view.rotate(FRAC_PI_2);
+        assert_close(
+            view.bounds(Vec2::new(400.0, 300.0)),
+            Vec2::new(300.0, 400.0),
+        );
+        view.rotate(FRAC_PI_2 / 2.0);
+        let diagonal = 700.0 * std::f32::consts::FRAC_1_SQRT_2;
+        assert_close(
+            view.bounds(Vec2::new(400.0, 300.0)),
+            Vec2::new(diagonal, diagonal),
+        );
+    }
+
+    #[test]
+    fn conversions_round_trip() {
+        let view = View {
+            center: Vec2::new(120.0, -30.0),
+            scale: 0.37,
+            rotation: 1.1,
+            flip_horizontal: true,
+            flip_vertical: false,
+        };
+        for p in [Vec2::zero(), Vec2::new(13.0, 7.5), Vec2::new(-400.0, 250.0)] {
+            assert_close(view.view_to_image(view.image_to_view(p)), p);
+            assert_close(
+                view.screen_to_image(view.image_to_screen(p, SURFACE), SURFACE),
+                p,
+            );
+        }
+    }
+
+    #[test]
+    fn pan_follows_the_screen() {
+        let mut view = View {
+            scale: 3.0,
+            rotation: 0.4,
+            flip_vertical: true,
+            ..View::centered(Vec2::new(64.0, 64.0))
+        };
+        let p = Vec2::new(10.0, 20.0);
+        let before = view.image_to_screen(p, SURFACE);
+        view.pan(Vec2::new(25.0, -5.0));
+        assert_close(
+            view.image_to_screen(p, SURFACE),
+            before + Vec2::new(25.0, -5.0),
+        );
+    }
+
+    #[test]
+    fn zoom_keeps_the_anchor() {
+        let mut view = View {
+            rotation: 0.7,
+            flip_horizontal: true,
+            ..View::centered(Vec2::new(300.0, 200.0))
+        };
+        let cursor = Vec2::new(620.0, 110.0);
+        let under = view.screen_to_image(cursor, SURFACE);
+        view.zoom_at(4.0, View::screen_to_view(cursor, SURFACE));
+        assert_close(view.screen_to_image(cursor, SURFACE), under);
+        assert!((view.scale - 4.0).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn aligned_pixels_start_on_screen_pixels() {
+        let view = View {
+            center: Vec2::new(10.3, 7.8),
+            scale: 3.0,
+            ..Default::default()
+        }
+        .pixel_aligned(Vec2::new(801.0, 600.0));
+        for p in [Vec2::zero(), Vec2::new(5.0, 9.0)] {
+            let screen = view.image_to_screen(p, Vec2::new(801.0, 600.0));
+            assert_close(screen, Vec2::new(screen.x.round(), screen.y.round()));
+        }
+    }
+
+    #[test]
+    fn clamp_keeps_the_image_on_screen() {
+        let size = Vec2::new(400.0, 1000.0);
+        // narrower than the surface but taller
+        let mut view = View {
+            center: Vec2::new(900.0, -300.0),
+            ..Default::default()
+        };
+        view.clamp(size, SURFACE);
+        assert_close(
+            view.image_to_screen(Vec2::zero(), SURFACE),
+            Vec2::new(200.0, 0.0),
+        );
+        view.pan(Vec2::new(0.0, -1000.0));
+        view.clamp(size, SURFACE);
+        assert_close(view.image_to_screen(size, SURFACE), Vec2::new(600.0, 600.0));
+        // rotated, the image is wider than the surface
+        view.rotate(FRAC_PI_2);
+        view.pan(Vec2::new(-5000.0, 0.0));
+        view.clamp(size, SURFACE);
+        assert_close(
+            view.image_to_screen(size / 2.0, SURFACE),
+            Vec2::new(300.0, 300.0),
+        );
+    }
+
+    #[test]
+    fn transform_matches_screen_coordinates() {
+        let view = View {
+            center: Vec2::new(30.0, 40.0),
+            scale: 1.5,
+            rotation: 0.3,
+            flip_horizontal: true,
+            flip_vertical: true,
+        };
+        let p = Vec2::new(12.0, 80.0);
+        let clip = view.transform(SURFACE) * Vec4::new(p.x, p.y, 0.0, 1.0);
+        let screen = view.image_to_screen(p, SURFACE);
+        assert_close(
+            Vec2::new(clip.x, clip.y),
+            Vec2::new(
+                screen.x / SURFACE.x * 2.0 - 1.0,
+                1.0 - screen.y / SURFACE.y * 2.0,
+            ),
+        );
+    }
+}
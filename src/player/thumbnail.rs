@@ -0,0 +1,157 @@
+pub mod cache;
+
+use super::playlist::{handler, DecodeOptions};
+use crate::Rgba8Image;
+use std::{
+    collections::{HashMap, VecDeque},
+    ops::Range,
+    path::PathBuf,
+    sync::{mpsc, Arc, Condvar, Mutex},
+    thread,
+};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Thumbnails fit in a square this many pixels wide.
+pub const SIZE: u32 = 128;
+
+/// Decodes thumbnails on background threads, going through the shared thumbnail cache.
+pub struct Thumbnailer {
+    queue: Arc<Queue>,
+    results: mpsc::Receiver<(usize, Option<Rgba8Image>)>,
+    /// Files of the items, to decode them again.
+    paths: HashMap<usize, PathBuf>,
+    pending: usize,
+}
+
+/// Work shared with the decoding threads.
+#[derive(Default)]
+struct Queue {
+    jobs: Mutex<Jobs>,
+    added: Condvar,
+}
+
+#[derive(Default)]
+struct Jobs {
+    /// Playlist indices and files waiting to be decoded, taken from the front.
+    waiting: VecDeque<(usize, PathBuf)>,
+    /// Whether the threads should stop.
+    closed: bool,
+}
+
+impl Thumbnailer {
+    pub fn new(jobs: Vec<(usize, PathBuf)>, options: DecodeOptions, auto_orient: bool) -> Self {
+        let pending = jobs.len();
+        let paths = jobs.iter().cloned().collect();
+        let queue = Arc::new(Queue::default());
+        queue.jobs.lock().unwrap().waiting = VecDeque::from(jobs);
+        let (sender, results) = mpsc::channel();
+        let threads = thread::available_parallelism().map_or(2, |n| n.get());
+        for _ in 0..threads {
+            let queue = Arc::clone(&queue);
+            let sender = sender.clone();
+            thread::spawn(move || loop {
+                let (index, path) = match queue.next() {
+                    Some(job) => job,
+                    None => return,
+                };
+                let thumbnail = match decode(path.clone(), options, auto_orient) {
+                    Ok(img) => Some(img),
+                    Err(e) => {
+                        eprintln!("{}: {}", path.display(), e);
+                        None
+                    }
+                };
+                if sender.send((index, thumbnail)).is_err() {
+                    return;
+                }
+            });
+        }
+        Self {
+            queue,
+            results,
+            paths,
+            pending,
+        }
+    }
+
+    /// Moves the items in `indices` to the front of the queue.
+    pub fn prioritize(&self, indices: Range<usize>) {
+        let jobs = &mut self.queue.jobs.lock().unwrap().waiting;
+        let (mut first, rest): (VecDeque<_>, VecDeque<_>) = jobs
+            .drain(..)
+            .partition(|(index, _)| indices.contains(index));
+        first.extend(rest);
+        *jobs = first;
+    }
+
+    /// Decodes item `index` again, ahead of everything else.
+    pub fn request(&mut self, index: usize) {
+        if let Some(path) = self.paths.get(&index) {
+            self.queue
+                .jobs
+                .lock()
+                .unwrap()
+                .waiting
+                .push_front((index, path.clone()));
+            self.queue.added.notify_one();
+            self.pending += 1;
+        }
+    }
+
+    /// Thumbnails decoded since the last call, `None` for items that couldn't be decoded.
+    pub fn ready(&mut self) -> Vec<(usize, Option<Rgba8Image>)> {
+        let ready: Vec<_> = self.results.try_iter().collect();
+        self.pending -= ready.len();
+        ready
+    }
+
+    /// Number of thumbnails still being decoded.
+    pub fn pending(&self) -> usize {
+        self.pending
+    }
+}
+
+impl Queue {
+    /// Waits for the next job, `None` once the threads should stop.
+    fn next(&self) -> Option<(usize, PathBuf)> {
+        let mut jobs = self.jobs.lock().unwrap();
+        loop {
+            if jobs.closed {
+                return None;
+            }
+            if let Some(job) = jobs.waiting.pop_front() {
+                return Some(job);
+            }
+            jobs = self.added.wait(jobs).unwrap();
+        }
+    }
+}
+
+impl Drop for Thumbnailer {
+    fn drop(&mut self) {
+        let mut jobs = self.queue.jobs.lock().unwrap();
+        jobs.waiting.clear();
+        jobs.closed = true;
+        self.queue.added.notify_all();
+    }
+}
+
+fn decode(path: PathBuf, options: DecodeOptions, auto_orient: bool) -> Result<Rgba8Image> {
+    // other programs store upright, color managed thumbnails, so the cache is only shared
+    // when decoding the same way
+    let shared = auto_orient && options == DecodeOptions::default();
+    if shared {
+        if let Some(img) = cache::load(&path, SIZE) {
+            return Ok(img);
+        }
+    }
+    let frames = handler::decode_item(&path, 0, options, auto_orient)?;
+    let frame = frames.first().ok_or("no frames")?;
+    let img = frame.buffer.thumbnail(SIZE);
+    if shared {
+        // the cache only saves time, a read-only one is fine
+        let _ = cache::store(&path, &img, frame.buffer.dimensions());
+    }
+    Ok(img)
+}
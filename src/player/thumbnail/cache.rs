@@ -0,0 +1,200 @@
+//! Thumbnails shared with other programs, as described by the freedesktop.org thumbnail
+//! managing standard.
+
+use crate::{Pixels, Rgba8Image};
+use md5::{Digest, Md5};
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Cursor},
+    path::{self, Component, Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Directories of the cache and the size of the thumbnails in them, smallest first.
+const FLAVORS: [(&str, u32); 2] = [("normal", 128), ("large", 256)];
+
+/// Characters kept as they are in file URIs, like GLib does.
+const URI_SAFE: &[u8] = b"-._~!$&'()*+,;=:@/";
+
+/// Looks for a valid thumbnail of `path` no smaller than `size`, written by any program,
+/// and scales it down to fit in `size` by `size`.
+pub fn load(path: &Path, size: u32) -> Option<Rgba8Image> {
+    let root = root()?;
+    let (uri, mtime) = (uri(path)?, mtime(path).ok()?);
+    FLAVORS
+        .iter()
+        .filter(|(_, flavor)| *flavor >= size)
+        .find_map(|(dir, _)| {
+            let file = root.join(dir).join(file_name(&uri));
+            read(&file, &uri, mtime).ok()
+        })
+        .map(|img| Pixels::from(img).thumbnail(size))
+}
+
+/// Stores `img`, a thumbnail of `path` which is `dimensions` large, in the smallest flavor
+/// that holds it.
+pub fn store(path: &Path, img: &Rgba8Image, dimensions: (u32, u32)) -> Result<()> {
+    let root = root().ok_or("no cache directory")?;
+    if path.starts_with(&root) {
+        return Err("thumbnails aren't thumbnailed".into());
+    }
+    let uri = uri(path).ok_or("no file URI")?;
+    let size = img.width().max(img.height());
+    let (dir, _) = FLAVORS
+        .iter()
+        .find(|(_, flavor)| *flavor >= size)
+        .ok_or("thumbnail too large")?;
+    let dir = root.join(dir);
+    create_dir(&dir)?;
+
+    let text = [
+        ("Thumb::URI", uri.clone()),
+        ("Thumb::MTime", mtime(path)?.to_string()),
+        ("Thumb::Size", fs::metadata(path)?.len().to_string()),
+        ("Thumb::Image::Width", dimensions.0.to_string()),
+        ("Thumb::Image::Height", dimensions.1.to_string()),
+        ("Software", "yume".to_owned()),
+    ];
+    // written next to the final name and renamed, so no program sees half a file
+    let file = dir.join(file_name(&uri));
+    let temp = dir.join(format!("{}.yume-{}", file_name(&uri), std::process::id()));
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(create_file(&temp)?),
+        img.width(),
+        img.height(),
+    );
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    for (keyword, value) in text {
+        encoder.add_text_chunk(keyword.to_owned(), value)?;
+    }
+    let written = encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(img.as_raw()));
+    if let Err(e) = written {
+        let _ = fs::remove_file(&temp);
+        return Err(e.into());
+    }
+    fs::rename(&temp, &file)?;
+    Ok(())
+}
+
+/// The thumbnail in `file` if it was made from the current version of `uri`.
+fn read(file: &Path, uri: &str, mtime: u64) -> Result<Rgba8Image> {
+    let data = fs::read(file)?;
+    let reader = png::Decoder::new(Cursor::new(&data)).read_info()?;
+    let text = &reader.info().uncompressed_latin1_text;
+    let value = |keyword: &str| {
+        text.iter()
+            .find(|chunk| chunk.keyword == keyword)
+            .map(|chunk| chunk.text.as_str())
+    };
+    if value("Thumb::URI") != Some(uri) {
+        return Err("thumbnail of another file".into());
+    }
+    if value("Thumb::MTime").and_then(|v| v.parse().ok()) != Some(mtime) {
+        return Err("outdated thumbnail".into());
+    }
+    Ok(image::load_from_memory_with_format(&data, image::ImageFormat::Png)?.to_rgba8())
+}
+
+fn root() -> Option<PathBuf> {
+    Some(dirs::cache_dir()?.join("thumbnails"))
+}
+
+fn file_name(uri: &str) -> String {
+    format!("{:x}.png", Md5::digest(uri.as_bytes()))
+}
+
+/// The `file://` URI of `path`, percent-encoded.
+///
+/// Like GLib, symbolic links are kept and only `.` and `..` are resolved, so the URI matches
+/// the one other programs use for the same path.
+fn uri(path: &Path) -> Option<String> {
+    let mut absolute = PathBuf::new();
+    for component in path::absolute(path).ok()?.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                absolute.pop();
+            }
+            c => absolute.push(c),
+        }
+    }
+    #[cfg(unix)]
+    let bytes = std::os::unix::ffi::OsStrExt::as_bytes(absolute.as_os_str());
+    #[cfg(not(unix))]
+    let bytes = absolute.to_str()?.as_bytes();
+
+    let mut uri = "file://".to_owned();
+    for &b in bytes {
+        if b.is_ascii_alphanumeric() || URI_SAFE.contains(&b) {
+            uri.push(b as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", b));
+        }
+    }
+    Some(uri)
+}
+
+/// Modification time in whole seconds since the epoch.
+fn mtime(path: &Path) -> Result<u64> {
+    Ok(fs::metadata(path)?
+        .modified()?
+        .duration_since(UNIX_EPOCH)?
+        .as_secs())
+}
+
+/// Creates `dir` and its parents, readable only by the user as the spec asks.
+fn create_dir(dir: &Path) -> Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(dir)?;
+    Ok(())
+}
+
+fn create_file(path: &Path) -> Result<File> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    Ok(options.open(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uris_escape_like_glib() {
+        let uri = uri(Path::new("/tmp/./yume/../yume a#b%.png")).unwrap();
+        assert_eq!(uri, "file:///tmp/yume%20a%23b%25.png");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn uris_keep_symlinks_and_raw_bytes() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+        let dir = std::env::temp_dir().join(format!("yume-uri-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let link = dir.join("link");
+        std::os::unix::fs::symlink(std::env::temp_dir(), &link).unwrap();
+        let path = link.join(OsStr::from_bytes(b"caf\xe9.png"));
+        let uri = uri(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(uri.ends_with("/link/caf%E9.png"), "{}", uri);
+    }
+
+    #[test]
+    fn names_thumbnails_like_the_spec() {
+        // the example from the thumbnail spec
+        assert_eq!(
+            file_name("file:///home/jens/photos/me.png"),
+            "c6ee772d9e49320e97ec29a7eb5b1697.png"
+        );
+    }
+}
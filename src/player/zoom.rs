@@ -0,0 +1,148 @@
+use serde::Deserialize;
+use ultraviolet::Vec2;
+
+/// How the scale is picked for each image and after the window is resized.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ZoomMode {
+    /// Whole image on screen.
+    Fit,
+    /// Like `Fit`, but small images stay at 100%.
+    #[default]
+    FitIfLarger,
+    /// Image covers the whole screen.
+    Fill,
+    FitWidth,
+    FitHeight,
+    /// 100%.
+    Actual,
+    /// The same scale for every image, changed by zooming.
+    Locked,
+}
+
+impl ZoomMode {
+    pub const ALL: &'static [Self] = &[
+        Self::Fit,
+        Self::FitIfLarger,
+        Self::Fill,
+        Self::FitWidth,
+        Self::FitHeight,
+        Self::Actual,
+        Self::Locked,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Fit => "fit",
+            Self::FitIfLarger => "fit-if-larger",
+            Self::Fill => "fill",
+            Self::FitWidth => "fit-width",
+            Self::FitHeight => "fit-height",
+            Self::Actual => "actual",
+            Self::Locked => "locked",
+        }
+    }
+
+    /// Scale for an image whose rotated bounds are `bounds` on a surface of `surface`.
+    pub fn scale(self, bounds: Vec2, surface: Vec2, locked: f32) -> f32 {
+        let width = surface.x / bounds.x;
+        let height = surface.y / bounds.y;
+        match self {
+            Self::Fit => width.min(height),
+            Self::FitIfLarger => width.min(height).min(1.0),
+            Self::Fill => width.max(height),
+            Self::FitWidth => width,
+            Self::FitHeight => height,
+            Self::Actual => 1.0,
+            Self::Locked => locked,
+        }
+    }
+}
+
+impl std::str::FromStr for ZoomMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .find(|mode| mode.name() == s)
+            .copied()
+            .ok_or_else(|| format!("unknown zoom mode {}", s))
+    }
+}
+
+/// Nearest scale at or below `scale` that is a whole number or one over a whole number.
+pub fn snap(scale: f32) -> f32 {
+    if scale >= 1.0 {
+        scale.floor()
+    } else {
+        1.0 / (1.0 / scale).ceil()
+    }
+}
+
+/// Moves `steps` whole scales along ..., 1/3, 1/2, 1, 2, 3, ...
+pub fn step(scale: f32, steps: i32) -> f32 {
+    let level = if scale >= 1.0 {
+        scale.round() as i32 - 1
+    } else {
+        1 - (1.0 / scale).round() as i32
+    };
+    let level = level + steps;
+    if level >= 0 {
+        (level + 1) as f32
+    } else {
+        1.0 / (1 - level) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scales_fit_the_surface() {
+        let surface = Vec2::new(800.0, 600.0);
+        let wide = Vec2::new(1600.0, 400.0);
+        let small = Vec2::new(200.0, 100.0);
+        let scale = |mode: ZoomMode, bounds| mode.scale(bounds, surface, 3.0);
+        assert_eq!(scale(ZoomMode::Fit, wide), 0.5);
+        assert_eq!(scale(ZoomMode::Fit, small), 4.0);
+        assert_eq!(scale(ZoomMode::FitIfLarger, wide), 0.5);
+        assert_eq!(scale(ZoomMode::FitIfLarger, small), 1.0);
+        assert_eq!(scale(ZoomMode::Fill, wide), 1.5);
+        assert_eq!(scale(ZoomMode::FitWidth, wide), 0.5);
+        assert_eq!(scale(ZoomMode::FitHeight, wide), 1.5);
+        assert_eq!(scale(ZoomMode::Actual, wide), 1.0);
+        assert_eq!(scale(ZoomMode::Locked, wide), 3.0);
+    }
+
+    #[test]
+    fn snaps_down_to_whole_scales() {
+        assert_eq!(snap(1.0), 1.0);
+        assert_eq!(snap(2.7), 2.0);
+        assert_eq!(snap(0.9), 0.5);
+        assert_eq!(snap(0.5), 0.5);
+        assert_eq!(snap(0.3), 0.25);
+    }
+
+    #[test]
+    fn steps_through_whole_scales() {
+        assert_eq!(step(1.0, 1), 2.0);
+        assert_eq!(step(2.0, 1), 3.0);
+        assert_eq!(step(1.0, -1), 0.5);
+        assert_eq!(step(0.5, -1), 1.0 / 3.0);
+        assert_eq!(step(0.5, 2), 2.0);
+        assert_eq!(step(3.0, -4), 1.0 / 3.0);
+        // scales in between count from the nearest whole one
+        assert_eq!(step(2.4, 1), 3.0);
+        assert_eq!(step(0.45, 1), 1.0);
+    }
+
+    #[test]
+    fn names_parse() {
+        for &mode in ZoomMode::ALL {
+            assert_eq!(mode.name().parse(), Ok(mode));
+        }
+        assert!("zoom".parse::<ZoomMode>().is_err());
+    }
+}